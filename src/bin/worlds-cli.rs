@@ -0,0 +1,210 @@
+// Command line tools, kept out of the GUI binary so that their output shows on Windows.
+
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use clap::{Parser, Subcommand};
+use worlds::{
+    biome_colors, check_settings, export_geojson, export_tiled, export_world, load_biomes,
+    load_settings, Projection, Rasterisation, Report, Settings, WorldGenerator, BIOMES_PATH,
+    MAX_HEX_TILE_WIDTH, MIN_HEX_TILE_WIDTH, MIN_MAP_SIZE,
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Validate a settings file and the biome definitions, exits non-zero on errors
+    Validate {
+        /// Directory of the biome definitions
+        #[arg(long, default_value = BIOMES_PATH)]
+        biomes: PathBuf,
+
+        /// Settings file, biome conditions are also checked against its layers
+        #[arg(long)]
+        settings: Option<PathBuf>,
+    },
+    /// Generate a world without a window and export its layers to a directory
+    Generate {
+        /// Settings or preset file, default settings otherwise
+        #[arg(long)]
+        settings: Option<PathBuf>,
+
+        /// World seed, overrides the one of the settings
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Width in tiles, overrides the one of the settings
+        #[arg(long, value_parser = clap::value_parser!(u32).range(MIN_MAP_SIZE as i64..))]
+        width: Option<u32>,
+
+        /// Height in tiles, overrides the one of the settings
+        #[arg(long, value_parser = clap::value_parser!(u32).range(MIN_MAP_SIZE as i64..))]
+        height: Option<u32>,
+
+        /// Map projection, overrides the one of the settings
+        #[arg(long)]
+        projection: Option<Projection>,
+
+        /// Directory of the biome definitions
+        #[arg(long, default_value = BIOMES_PATH)]
+        biomes: PathBuf,
+
+        /// Directory the PNG previews and raw layers are written to
+        #[arg(long, short, default_value = "output")]
+        output: PathBuf,
+
+        /// Draw tiles as hexagons of this width in pixels, instead of one pixel per tile
+        #[arg(
+            long,
+            value_name = "TILE_WIDTH",
+            value_parser = clap::value_parser!(u32)
+                .range(MIN_HEX_TILE_WIDTH as i64..=MAX_HEX_TILE_WIDTH as i64),
+        )]
+        hexagons: Option<u32>,
+
+        /// Also write a Tiled map of the biomes
+        #[arg(long)]
+        tiled: bool,
+
+        /// Also write the coastlines and biome regions as GeoJSON
+        #[arg(long)]
+        geojson: bool,
+    },
+}
+
+fn main() -> AppExit {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Validate { biomes, settings } => validate(&biomes, settings.as_deref()),
+        Command::Generate {
+            settings,
+            seed,
+            width,
+            height,
+            projection,
+            biomes,
+            output,
+            hexagons,
+            tiled,
+            geojson,
+        } => {
+            let settings = match settings {
+                Some(path) => match load_settings(&path) {
+                    Ok(settings) => settings,
+                    Err(e) => {
+                        eprintln!("{}: {}", path.display(), e);
+                        return AppExit::error();
+                    }
+                },
+                None => Settings::default(),
+            };
+            let settings = Settings {
+                world_seed: seed.unwrap_or(settings.world_seed),
+                width: width.unwrap_or(settings.width),
+                height: height.unwrap_or(settings.height),
+                projection: projection.unwrap_or(settings.projection),
+                ..settings
+            };
+            // The overrides are checked like the settings file
+            let errors = check_settings(&settings);
+            if !errors.is_empty() {
+                for error in &errors {
+                    eprintln!("Invalid settings: {}", error);
+                }
+                return AppExit::error();
+            }
+            let rasterisation = match hexagons {
+                Some(tile_width) => Rasterisation::Hexagons { tile_width },
+                None => Rasterisation::PixelPerTile,
+            };
+            generate(settings, &biomes, rasterisation, tiled, geojson, &output)
+        }
+    }
+}
+
+fn validate(biomes: &Path, settings: Option<&Path>) -> AppExit {
+    let mut report = Report::default();
+
+    let settings = settings
+        .and_then(|path| report.validate_settings(path))
+        .unwrap_or_default();
+    if let Err(e) = report.validate_biomes(biomes, &settings) {
+        eprintln!("{}: {}", biomes.display(), e);
+        return AppExit::error();
+    }
+
+    for issue in &report.issues {
+        eprintln!("{}", issue);
+    }
+    if report.is_ok() {
+        println!("Everything is valid");
+        AppExit::Success
+    } else {
+        eprintln!("{} error(s) found", report.issues.len());
+        AppExit::error()
+    }
+}
+
+fn generate(
+    settings: Settings,
+    biomes: &Path,
+    rasterisation: Rasterisation,
+    tiled: bool,
+    geojson: bool,
+    output: &Path,
+) -> AppExit {
+    let biomes = match load_biomes(biomes) {
+        Ok(biomes) => biomes,
+        Err(e) => {
+            eprintln!("{}: {}", biomes.display(), e);
+            return AppExit::error();
+        }
+    };
+
+    println!(
+        "Generating a {}x{} world with seed {}",
+        settings.width, settings.height, settings.world_seed
+    );
+    let world = WorldGenerator::new(settings.clone())
+        .with_biomes(biomes.values().cloned())
+        .generate()
+        .expect("a generation without progress is never cancelled");
+    for (name, share) in world.biome_names.iter().zip(world.biome_shares(&settings)) {
+        println!("{}: {:.1}% of the globe", name, share * 100.);
+    }
+
+    let mut written = export_world(&world, &settings, &biomes, rasterisation, output);
+    if tiled {
+        let biome_colors = biome_colors(&world, &biomes);
+        written = written.and_then(|mut paths| {
+            paths.extend(export_tiled(&world, &settings, &biome_colors, output)?);
+            Ok(paths)
+        });
+    }
+    if geojson {
+        written = written.and_then(|mut paths| {
+            paths.push(export_geojson(&world, &settings, output)?);
+            Ok(paths)
+        });
+    }
+
+    match written {
+        Ok(paths) => {
+            for path in paths {
+                println!("Written {}", path.display());
+            }
+            AppExit::Success
+        }
+        Err(e) => {
+            eprintln!("{}: {}", output.display(), e);
+            AppExit::error()
+        }
+    }
+}
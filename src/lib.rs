@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use bevy::{
     asset::load_internal_binary_asset,
     prelude::*,
@@ -6,7 +8,40 @@ use bevy::{
 
 mod worlds;
 
-pub struct AppPlugin;
+// Headless generation, usable without a Bevy `App`.
+pub use worlds::export::geojson::{export_geojson, world_geojson};
+pub use worlds::export::tiled::{export_tiled, import_tiled, parse_tiled, ImportTiledError};
+pub use worlds::export::{
+    biome_colors, export_world, ExportError, Layer, Rasterisation, MAX_HEX_TILE_WIDTH,
+    MIN_HEX_TILE_WIDTH,
+};
+pub use worlds::map::biomes::{load_biome, load_biomes, Biome, LoadBiomeError, BIOMES_PATH};
+pub use worlds::map::generator::world::{Cancelled, GenerationProgress, World, WorldGenerator};
+pub use worlds::projection::Projection;
+pub use worlds::save::{load_world, read_world, save_world, write_world, SaveError};
+pub use worlds::settings::{
+    check_settings, load_settings, save_settings, InvalidSetting, Settings, MIN_MAP_SIZE,
+};
+pub use worlds::validation::{Issue, Report};
+
+// Bevy plugin, with the resources and events of the worlds it generates, and
+// the tile values of its layer grids.
+pub use worlds::map::events::{DrawMapEvent, GenerateMapEvent, MapGenerated};
+pub use worlds::map::generator::{
+    biome::{BiomeId, TileBiome},
+    elevation::TileElevation,
+    temperature::TileTemperature,
+};
+pub use worlds::map::inspect::{inspect_tile, InspectedTile, TileInspection};
+pub use worlds::map::layers::LayerGrid;
+pub use worlds::map::{GeneratedWorld, GenerationTask, MapState, PendingWorld};
+pub use worlds::{WorldsAssets, WorldsPlugin};
+
+#[derive(Default)]
+pub struct AppPlugin {
+    /// Settings file to load at startup, defaults to `settings.toml`.
+    pub settings_path: Option<PathBuf>,
+}
 
 impl Plugin for AppPlugin {
     fn build(&self, app: &mut App) {
@@ -47,7 +82,11 @@ impl Plugin for AppPlugin {
         );
 
         // Add Worlds plugin.
-        app.add_plugins(worlds::plugin);
+        let mut worlds_plugin = WorldsPlugin::new();
+        if let Some(path) = &self.settings_path {
+            worlds_plugin = worlds_plugin.with_settings_path(path.clone());
+        }
+        app.add_plugins(worlds_plugin);
     }
 }
 
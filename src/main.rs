@@ -1,9 +1,26 @@
 // Disable console on Windows for non-dev builds.
 #![cfg_attr(not(feature = "dev"), windows_subsystem = "windows")]
 
+use std::path::PathBuf;
+
 use bevy::prelude::*;
+use clap::Parser;
 use worlds::AppPlugin;
 
+/// Validating and generating worlds without a window is done by `worlds-cli`.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Settings file to load at startup, `settings.toml` by default
+    settings: Option<PathBuf>,
+}
+
 fn main() -> AppExit {
-    App::new().add_plugins(AppPlugin).run()
+    let cli = Cli::parse();
+
+    App::new()
+        .add_plugins(AppPlugin {
+            settings_path: cli.settings,
+        })
+        .run()
 }
@@ -1,11 +1,164 @@
-use bevy::app::App;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use map::biomes::BIOMES_PATH;
+use map::chunks::ChunkCache;
+use settings::{Settings, SettingsPath, DEFAULT_SETTINGS_PATH, PRESETS_PATH};
 
 pub mod camera;
+pub mod export;
+pub mod hex;
 pub mod map;
+pub mod projection;
+pub mod save;
+pub mod seed;
 pub mod settings;
+pub mod sphere;
 pub mod ui;
 pub mod utils;
+pub mod validation;
+
+pub const TILE_TEXTURE_PATH: &str = "tiles_white.png";
+
+/// Generates and renders a world in a host `App`.
+///
+/// The host is expected to provide the Bevy plugins (`DefaultPlugins` or
+/// equivalent), everything else is opt-in or opt-out through the builder.
+pub struct WorldsPlugin {
+    settings: Option<Settings>,
+    settings_path: Option<PathBuf>,
+    chunk_cache: Option<ChunkCache>,
+    assets: WorldsAssets,
+    camera: bool,
+    inspector: bool,
+    ui: bool,
+}
+
+/// Where the plugin finds its files.
+#[derive(Resource, Debug, Clone)]
+pub struct WorldsAssets {
+    /// Directory of the biome definitions.
+    pub biomes: PathBuf,
+    /// Directory of the settings presets.
+    pub presets: PathBuf,
+    /// Tile texture, as an asset path.
+    pub tile_texture: String,
+}
+
+impl Default for WorldsAssets {
+    fn default() -> Self {
+        Self {
+            biomes: PathBuf::from(BIOMES_PATH),
+            presets: PathBuf::from(PRESETS_PATH),
+            tile_texture: TILE_TEXTURE_PATH.to_string(),
+        }
+    }
+}
+
+impl Default for WorldsPlugin {
+    fn default() -> Self {
+        Self {
+            settings: None,
+            settings_path: None,
+            chunk_cache: None,
+            assets: WorldsAssets::default(),
+            camera: true,
+            inspector: true,
+            ui: true,
+        }
+    }
+}
+
+impl WorldsPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Initial settings, instead of the ones loaded from the settings file.
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// Settings file loaded at startup and saved from the UI, defaults to `settings.toml`.
+    pub fn with_settings_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings_path = Some(path.into());
+        self
+    }
+
+    /// Directory streamed chunks are cached to, `None` to always generate
+    /// them. Defaults to `cache/chunks` next to the settings file.
+    pub fn with_chunk_cache(mut self, dir: Option<PathBuf>) -> Self {
+        self.chunk_cache = Some(ChunkCache(dir));
+        self
+    }
+
+    pub fn with_biomes_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.assets.biomes = path.into();
+        self
+    }
+
+    pub fn with_presets_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.assets.presets = path.into();
+        self
+    }
+
+    pub fn with_tile_texture(mut self, path: impl Into<String>) -> Self {
+        self.assets.tile_texture = path.into();
+        self
+    }
+
+    /// Whether to spawn a panning camera, disable it when the host has its own.
+    pub fn with_camera(mut self, camera: bool) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    /// Whether to show the settings inspector.
+    pub fn with_inspector(mut self, inspector: bool) -> Self {
+        self.inspector = inspector;
+        self
+    }
+
+    /// Whether to show the "World" window (presets, save, world codes).
+    pub fn with_ui(mut self, ui: bool) -> Self {
+        self.ui = ui;
+        self
+    }
+}
+
+impl Plugin for WorldsPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(settings) = &self.settings {
+            app.insert_resource(settings.clone());
+        }
+        if let Some(path) = &self.settings_path {
+            app.insert_resource(SettingsPath(path.clone()));
+        }
+        let settings_path = self
+            .settings_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SETTINGS_PATH));
+        app.insert_resource(
+            self.chunk_cache
+                .clone()
+                .unwrap_or_else(|| ChunkCache::next_to(&settings_path)),
+        );
+        app.insert_resource(self.assets.clone());
 
-pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((ui::plugin, camera::plugin, settings::plugin, map::plugin));
+        app.add_plugins((settings::plugin, map::plugin));
+        if self.camera {
+            app.add_plugins(camera::plugin);
+        }
+        if self.inspector || self.ui {
+            app.add_plugins(ui::egui_plugin);
+        }
+        if self.inspector {
+            app.add_plugins(ui::inspector_plugin);
+        }
+        if self.ui {
+            app.add_plugins(ui::plugin);
+        }
+    }
 }
@@ -0,0 +1,571 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{create_dir_all, write},
+    io,
+    path::{Path, PathBuf},
+};
+
+use std::ops::DerefMut;
+
+use bevy::color::ColorToPacked;
+use bevy::math::{URect, UVec2};
+use bevy_ecs_tilemap::tiles::TilePos;
+use image::{ImageBuffer, Luma, Pixel, Rgba, RgbaImage};
+
+use super::map::{
+    biomes::Biome,
+    generator::{
+        elevation::{ElevationGenerator, TileElevation},
+        temperature::{TemperatureGenerator, TileTemperature},
+        world::World,
+        MapGenerator,
+    },
+    renderer::{elevation::ElevationMapRenderer, temperature::TemperatureMapRenderer, tile_color},
+};
+use super::settings::{save_settings, Settings, SettingsFileError};
+use super::utils::tile_lonlat;
+
+pub mod geojson;
+pub mod tiled;
+
+/// Directory the UI exports to.
+pub const EXPORT_PATH: &str = "export";
+
+/// Raw value of tiles without biome in `biome.bin`.
+pub const NO_BIOME: u16 = u16::MAX;
+
+/// Widths of the hexagons accepted from the command line and the UI, in pixels.
+pub const MIN_HEX_TILE_WIDTH: u32 = 2;
+pub const MAX_HEX_TILE_WIDTH: u32 = 64;
+/// Largest side of a hexagon image, the hexagons shrink to fit.
+pub const MAX_IMAGE_SIDE: u32 = 16384;
+
+/// Single-channel 16-bit image, see [`gray16_image`].
+pub type Gray16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+/// How tiles are laid out in the images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rasterisation {
+    /// One pixel per tile, odd and even rows aligned.
+    #[default]
+    PixelPerTile,
+    /// Regular pointy-top hexagons `tile_width` pixels wide, with the even rows
+    /// shifted by half a tile like the tilemap. Pixels outside the map are empty.
+    ///
+    /// The hexagons are narrower when the image would be more than
+    /// [`MAX_IMAGE_SIDE`] pixels wide or high, and at least a pixel wide.
+    Hexagons { tile_width: u32 },
+}
+
+impl Rasterisation {
+    /// Size of the image of a map of `map_size` tiles.
+    pub fn dimensions(&self, map_size: UVec2) -> (u32, u32) {
+        match self.hexagon_size(map_size) {
+            None => (map_size.x, map_size.y),
+            Some((width, height)) => (
+                (width * (map_size.x as f64 + 0.5)).ceil() as u32,
+                (height * (0.75 * (map_size.y as f64 - 1.) + 1.)).ceil() as u32,
+            ),
+        }
+    }
+
+    /// Width and height of the hexagons for a map of `map_size` tiles, `None`
+    /// for a pixel per tile.
+    fn hexagon_size(&self, map_size: UVec2) -> Option<(f64, f64)> {
+        let Rasterisation::Hexagons { tile_width } = *self else {
+            return None;
+        };
+        let height_ratio = 2. / 3f64.sqrt();
+        let max_side = MAX_IMAGE_SIDE as f64;
+        let max_width = (max_side / (map_size.x as f64 + 0.5))
+            .min(max_side / ((0.75 * (map_size.y as f64 - 1.) + 1.).max(1.) * height_ratio))
+            .floor() as u32;
+        let width = tile_width.min(max_width).max(1) as f64;
+        Some((width, width * height_ratio))
+    }
+
+    /// Index of the tile covering a pixel, row by row like `TilePos::to_index`,
+    /// the first row of tiles at the bottom.
+    pub fn tile_index(&self, map_size: UVec2, x: u32, y: u32) -> Option<usize> {
+        let row = |image_row: i64| map_size.y as i64 - 1 - image_row;
+        let tile_index = |column: i64, row: i64| {
+            let in_map =
+                (0..map_size.x as i64).contains(&column) && (0..map_size.y as i64).contains(&row);
+            in_map.then(|| (row * map_size.x as i64 + column) as usize)
+        };
+
+        let Some((width, height)) = self.hexagon_size(map_size) else {
+            return tile_index(x as i64, row(y as i64));
+        };
+        let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+
+        // Hexagons are the cells of their nearest centre, out of the map included
+        let first_row = ((py - height / 2.) / (0.75 * height)).floor() as i64;
+        let mut nearest = (f64::INFINITY, 0, 0);
+        for image_row in first_row - 1..=first_row + 2 {
+            let row = row(image_row);
+            let shift = if row.rem_euclid(2) == 0 {
+                width / 2.
+            } else {
+                0.
+            };
+            let cy = height / 2. + image_row as f64 * 0.75 * height;
+            let column = ((px - width / 2. - shift) / width).round() as i64;
+            for column in column - 1..=column + 1 {
+                let cx = width / 2. + shift + column as f64 * width;
+                let distance = (px - cx).powi(2) + (py - cy).powi(2);
+                if distance < nearest.0 {
+                    nearest = (distance, column, row);
+                }
+            }
+        }
+
+        tile_index(nearest.1, nearest.2)
+    }
+
+    /// Pixels covered by a rectangle of tiles, the max corner excluded.
+    pub fn pixel_rect(&self, map_size: UVec2, tiles: URect) -> URect {
+        let (image_width, image_height) = self.dimensions(map_size);
+        // Image rows go down from the last row of tiles
+        let (top, bottom) = (map_size.y - tiles.max.y, map_size.y - tiles.min.y);
+        let pixels = match self.hexagon_size(map_size) {
+            None => URect::new(tiles.min.x, top, tiles.max.x, bottom),
+            Some((width, height)) => URect::new(
+                (tiles.min.x as f64 * width).floor() as u32,
+                (top as f64 * 0.75 * height).floor() as u32,
+                ((tiles.max.x as f64 + 0.5) * width).ceil() as u32,
+                ((bottom as f64 - 1.) * 0.75 * height + height).ceil() as u32,
+            ),
+        };
+        pixels.intersect(URect::new(0, 0, image_width, image_height))
+    }
+}
+
+/// Draws every tile of the world with its pixel, empty pixels are `background`.
+pub fn rasterise<P: Pixel>(
+    world: &World,
+    rasterisation: Rasterisation,
+    background: P,
+    pixel: impl Fn(usize) -> P,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let map_size = UVec2::new(world.width, world.height);
+    let (width, height) = rasterisation.dimensions(map_size);
+    ImageBuffer::from_fn(width, height, |x, y| {
+        rasterisation
+            .tile_index(map_size, x, y)
+            .map_or(background, &pixel)
+    })
+}
+
+/// Redraws the pixels of a rectangle of tiles in an image of
+/// [`Rasterisation::dimensions`], so that edits only redraw what they touch.
+pub fn rasterise_tiles<P: Pixel, C: DerefMut<Target = [P::Subpixel]>>(
+    image: &mut ImageBuffer<P, C>,
+    map_size: UVec2,
+    rasterisation: Rasterisation,
+    tiles: URect,
+    background: P,
+    pixel: impl Fn(usize) -> P,
+) {
+    let pixels = rasterisation.pixel_rect(map_size, tiles);
+    for y in pixels.min.y..pixels.max.y {
+        for x in pixels.min.x..pixels.max.x {
+            let color = rasterisation
+                .tile_index(map_size, x, y)
+                .map_or(background, &pixel);
+            image.put_pixel(x, y, color);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// The layers enabled in the settings, coloured like on screen.
+    Map,
+    Elevation,
+    Temperature,
+    Biome,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 4] = [
+        Layer::Map,
+        Layer::Elevation,
+        Layer::Temperature,
+        Layer::Biome,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Map => "map",
+            Layer::Elevation => "elevation",
+            Layer::Temperature => "temperature",
+            Layer::Biome => "biome",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Image(image::ImageError),
+    Settings(SettingsFileError),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "File error: {}", e),
+            ExportError::Image(e) => write!(f, "Image error: {}", e),
+            ExportError::Settings(e) => write!(f, "Settings error: {}", e),
+        }
+    }
+}
+
+/// Preview colour of each biome of the world, the average of its tile colours.
+pub fn biome_colors(world: &World, biomes: &HashMap<String, Biome>) -> Vec<[u8; 3]> {
+    world
+        .biome_names
+        .iter()
+        .map(|name| {
+            let tiles = biomes.get(name).and_then(|biome| biome.tiles.as_ref());
+            let Some(tiles) = tiles.filter(|tiles| !tiles.is_empty()) else {
+                return [0, 0, 0];
+            };
+            let mut sum = [0u32; 3];
+            for color in tiles.values() {
+                for (sum, &channel) in sum.iter_mut().zip(color) {
+                    *sum += channel as u32;
+                }
+            }
+            sum.map(|sum| (sum / tiles.len() as u32) as u8)
+        })
+        .collect()
+}
+
+/// Colourises a layer with its renderer, tiles out of the projected globe are
+/// left empty.
+pub fn layer_image(
+    world: &World,
+    settings: &Settings,
+    layer: Layer,
+    biome_colors: &[[u8; 3]],
+    rasterisation: Rasterisation,
+) -> RgbaImage {
+    rasterise(world, rasterisation, Rgba([0, 0, 0, 0]), |index| {
+        let tile_pos = world.tile_pos(index);
+        if tile_lonlat(settings, tile_pos.x, tile_pos.y).is_none() {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let elevation = TileElevation(world.elevation[index]);
+        let temperature = TileTemperature(world.temperature[index]);
+        let color = match layer {
+            Layer::Map => return map_pixel(settings, &tile_pos, &elevation, &temperature),
+            Layer::Elevation => ElevationMapRenderer.get_color(&elevation),
+            Layer::Temperature => TemperatureMapRenderer.get_color(&temperature, settings),
+            Layer::Biome => {
+                let [r, g, b] = world.biome[index]
+                    .and_then(|id| biome_colors.get(id as usize))
+                    .copied()
+                    .unwrap_or([0, 0, 0]);
+                return Rgba([r, g, b, 255]);
+            }
+        };
+        Rgba(color.to_srgba().to_u8_array())
+    })
+}
+
+/// Pixel of a tile on the map, coloured like the tilemap, empty out of the
+/// projected globe.
+pub fn map_pixel(
+    settings: &Settings,
+    tile_pos: &TilePos,
+    elevation: &TileElevation,
+    temperature: &TileTemperature,
+) -> Rgba<u8> {
+    if tile_lonlat(settings, tile_pos.x, tile_pos.y).is_none() {
+        return Rgba([0, 0, 0, 0]);
+    }
+    Rgba(
+        tile_color(settings, elevation, temperature)
+            .to_srgba()
+            .to_u8_array(),
+    )
+}
+
+/// Maps values linearly from `[min, max]` to `[1, 65535]`, empty pixels are 0:
+/// a pixel `p` is the value `min + (p - 1) / 65534 * (max - min)`.
+pub fn gray16_image(
+    world: &World,
+    values: &[f64],
+    [min, max]: [f64; 2],
+    rasterisation: Rasterisation,
+) -> Gray16Image {
+    rasterise(world, rasterisation, Luma([0]), |index| {
+        let normalized = ((values[index] - min) / (max - min)).clamp(0., 1.);
+        Luma([1 + (normalized * 65534.).round() as u16])
+    })
+}
+
+/// Encodes values as `f32`, the little-endian bytes of each one in the RGBA
+/// channels of its pixel, so that a PNG holds them unchanged. Empty pixels are
+/// NaN.
+pub fn float_image(world: &World, values: &[f64], rasterisation: Rasterisation) -> RgbaImage {
+    rasterise(
+        world,
+        rasterisation,
+        Rgba(f32::NAN.to_le_bytes()),
+        |index| Rgba((values[index] as f32).to_le_bytes()),
+    )
+}
+
+/// Writes the layers and the settings to a directory.
+///
+/// Every layer is written as a coloured PNG, elevation and temperature also as
+/// 16-bit grayscale PNGs over the range of the layer (elevation is a heightmap),
+/// see [`gray16_image`], whose `[min, max]` are written to `ranges.toml`.
+/// Temperature is also written unquantised to `temperature_f32.png`, see
+/// [`float_image`].
+/// Raw layers are little-endian and row by row from the first row of tiles:
+/// `f64` for elevation and temperature, `u16` biome ids ([`NO_BIOME`] for none)
+/// whose names are listed in `biomes.txt`.
+pub fn export_world(
+    world: &World,
+    settings: &Settings,
+    biomes: &HashMap<String, Biome>,
+    rasterisation: Rasterisation,
+    dir: &Path,
+) -> Result<Vec<PathBuf>, ExportError> {
+    create_dir_all(dir).map_err(ExportError::Io)?;
+    let mut written = Vec::new();
+
+    let colors = biome_colors(world, biomes);
+    for layer in Layer::ALL {
+        let path = dir.join(format!("{}.png", layer.name()));
+        layer_image(world, settings, layer, &colors, rasterisation)
+            .save(&path)
+            .map_err(ExportError::Image)?;
+        written.push(path);
+    }
+
+    let mut ranges = toml::Table::new();
+    for (name, values, range) in [
+        (
+            "elevation",
+            &world.elevation,
+            ElevationGenerator::get_min_max(settings),
+        ),
+        (
+            "temperature",
+            &world.temperature,
+            TemperatureGenerator::get_min_max(settings),
+        ),
+    ] {
+        let path = dir.join(format!("{}_16bit.png", name));
+        gray16_image(world, values, range, rasterisation)
+            .save(&path)
+            .map_err(ExportError::Image)?;
+        written.push(path);
+        ranges.insert(name.to_string(), range.to_vec().into());
+    }
+    let path = dir.join("temperature_f32.png");
+    float_image(world, &world.temperature, rasterisation)
+        .save(&path)
+        .map_err(ExportError::Image)?;
+    written.push(path);
+
+    let f64_bytes = |values: &[f64]| -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    };
+    let biome_bytes: Vec<u8> = world
+        .biome
+        .iter()
+        .flat_map(|id| id.unwrap_or(NO_BIOME).to_le_bytes())
+        .collect();
+    let mut biome_names = world.biome_names.join("\n");
+    biome_names.push('\n');
+
+    for (name, bytes) in [
+        ("elevation.bin", f64_bytes(&world.elevation)),
+        ("temperature.bin", f64_bytes(&world.temperature)),
+        ("biome.bin", biome_bytes),
+        ("biomes.txt", biome_names.into_bytes()),
+        ("ranges.toml", ranges.to_string().into_bytes()),
+    ] {
+        let path = dir.join(name);
+        write(&path, bytes).map_err(ExportError::Io)?;
+        written.push(path);
+    }
+
+    let path = dir.join("settings.toml");
+    save_settings(settings, &path).map_err(ExportError::Settings)?;
+    written.push(path);
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::read, path::Path};
+
+    use bevy::math::{URect, UVec2};
+    use bevy_ecs_tilemap::tiles::TilePos;
+    use image::Luma;
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use crate::worlds::map::biomes::{load_biomes, BIOMES_PATH};
+    use crate::worlds::map::generator::{
+        temperature::TemperatureGenerator,
+        world::{World, WorldGenerator},
+        MapGenerator,
+    };
+    use crate::worlds::settings::{load_settings, Settings};
+
+    use super::{export_world, rasterise, rasterise_tiles, Rasterisation, MAX_IMAGE_SIDE};
+
+    #[rstest]
+    fn test_export_world() {
+        let settings = Settings {
+            width: 12,
+            height: 8,
+            ..Settings::from_seed(1234)
+        };
+        let biomes = load_biomes(Path::new(BIOMES_PATH)).unwrap();
+        let world = WorldGenerator::new(settings.clone())
+            .with_biomes(biomes.values().cloned())
+            .generate()
+            .unwrap();
+        let dir = tempdir().unwrap();
+
+        export_world(
+            &world,
+            &settings,
+            &biomes,
+            Rasterisation::PixelPerTile,
+            dir.path(),
+        )
+        .unwrap();
+
+        let image = image::open(dir.path().join("elevation.png")).unwrap();
+        assert_eq!((image.width(), image.height()), (12, 8));
+        let heightmap = image::open(dir.path().join("elevation_16bit.png")).unwrap();
+        assert!(heightmap.as_luma16().is_some());
+        // The first row of tiles is the last of the image, its values within
+        // a step of the range
+        let temperature = image::open(dir.path().join("temperature_16bit.png"))
+            .unwrap()
+            .into_luma16();
+        let ranges: toml::Table =
+            toml::from_str(&std::fs::read_to_string(dir.path().join("ranges.toml")).unwrap())
+                .unwrap();
+        let range: Vec<f64> = ranges["temperature"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap())
+            .collect();
+        assert_eq!(range, TemperatureGenerator::get_min_max(&settings));
+        let pixel = temperature.get_pixel(0, 7).0[0];
+        let decoded = range[0] + (pixel - 1) as f64 / 65534. * (range[1] - range[0]);
+        assert!((decoded - world.temperature[0]).abs() <= (range[1] - range[0]) / 65534.);
+        let temperature = image::open(dir.path().join("temperature_f32.png"))
+            .unwrap()
+            .into_rgba8();
+        assert_eq!(
+            f32::from_le_bytes(temperature.get_pixel(0, 7).0),
+            world.temperature[0] as f32
+        );
+        let elevation = read(dir.path().join("elevation.bin")).unwrap();
+        assert_eq!(elevation.len(), 12 * 8 * 8);
+        assert_eq!(elevation[..8], world.elevation[0].to_le_bytes());
+        let biome = read(dir.path().join("biome.bin")).unwrap();
+        assert_eq!(biome.len(), 12 * 8 * 2);
+        assert_eq!(
+            load_settings(&dir.path().join("settings.toml")).unwrap(),
+            settings
+        );
+    }
+
+    #[rstest]
+    fn test_hexagons_rasterisation() {
+        let world = World {
+            width: 4,
+            height: 3,
+            ..Default::default()
+        };
+        let map_size = UVec2::new(4, 3);
+        let rasterisation = Rasterisation::Hexagons { tile_width: 10 };
+        let (width, height) = rasterisation.dimensions(map_size);
+        assert_eq!((width, height), (45, 29));
+        // Hexagons shrink to fit huge maps, and never vanish
+        let huge = Rasterisation::Hexagons { tile_width: 64 }.dimensions(UVec2::new(2000, 1000));
+        assert!(huge.0 <= MAX_IMAGE_SIDE && huge.1 <= MAX_IMAGE_SIDE);
+        assert_eq!(
+            Rasterisation::Hexagons { tile_width: 0 }.dimensions(map_size),
+            (5, 3)
+        );
+
+        // Top row is the last, even, row and is shifted by half a tile
+        assert_eq!(rasterisation.tile_index(map_size, 0, 5), None);
+        assert_eq!(
+            rasterisation.tile_index(map_size, 10, 5),
+            Some(world.index(&TilePos { x: 0, y: 2 }))
+        );
+        // Centre of the first tile, on the bottom row
+        assert_eq!(rasterisation.tile_index(map_size, 10, 23), Some(0));
+        // The middle, odd, row is not shifted
+        assert_eq!(
+            rasterisation.tile_index(map_size, 0, 14),
+            Some(world.index(&TilePos { x: 0, y: 1 }))
+        );
+        assert_eq!(rasterisation.tile_index(map_size, 44, 14), None);
+    }
+
+    #[rstest]
+    #[case::pixels(Rasterisation::PixelPerTile)]
+    #[case::hexagons(Rasterisation::Hexagons { tile_width: 6 })]
+    fn test_rasterise_tiles_redraws_their_pixels(#[case] rasterisation: Rasterisation) {
+        let world = World {
+            width: 5,
+            height: 4,
+            ..Default::default()
+        };
+        let map_size = UVec2::new(5, 4);
+        let mut image = rasterise(&world, rasterisation, Luma([0u16]), |_| Luma([1]));
+
+        let tiles = URect::new(1, 1, 3, 2);
+        rasterise_tiles(
+            &mut image,
+            map_size,
+            rasterisation,
+            tiles,
+            Luma([0]),
+            |index| Luma([2 + index as u16]),
+        );
+
+        let pixels = rasterisation.pixel_rect(map_size, tiles);
+        let in_rect = |rect: URect, x: u32, y: u32| {
+            (rect.min.x..rect.max.x).contains(&x) && (rect.min.y..rect.max.y).contains(&y)
+        };
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let Some(index) = rasterisation.tile_index(map_size, x, y) else {
+                continue;
+            };
+            let tile_pos = world.tile_pos(index);
+            let redrawn = Luma([2 + index as u16]);
+            if in_rect(tiles, tile_pos.x, tile_pos.y) {
+                assert_eq!(*pixel, redrawn);
+            } else if in_rect(pixels, x, y) {
+                // Pixels of the neighbours around the tiles may be redrawn too
+                assert!(*pixel == redrawn || *pixel == Luma([1]));
+            } else {
+                assert_eq!(*pixel, Luma([1]));
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! GeoJSON outlines of the world: coastlines, lakes and biome regions traced
+//! along the edges of the hexagons, in the longitude and latitude of the tiles.
+//!
+//! Rivers will be added as features once they are generated.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::write,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use bevy_ecs_tilemap::map::TilemapSize;
+
+use crate::worlds::hex;
+use crate::worlds::map::generator::world::World;
+use crate::worlds::settings::Settings;
+use crate::worlds::utils::position_to_lonlat;
+
+use super::ExportError;
+
+pub const GEOJSON_FILE: &str = "world.geojson";
+
+/// Tiles at or above this elevation are land.
+pub const SEA_LEVEL: f64 = 0.;
+
+/// Corner of a hexagon, `x` in half tiles and `y` in thirds of a row so that
+/// corners shared by several tiles are equal.
+type Corner = (i64, i64);
+
+/// Corners around the centre of a tile, counterclockwise from the lower right.
+/// The edge `i` goes from the corner `i` to the next one, towards the
+/// neighbour in the direction `i`.
+const CORNERS: [Corner; 6] = [(1, -1), (1, 1), (0, 2), (-1, 1), (-1, -1), (0, -2)];
+
+pub fn export_geojson(
+    world: &World,
+    settings: &Settings,
+    dir: &Path,
+) -> Result<PathBuf, ExportError> {
+    let path = dir.join(GEOJSON_FILE);
+    let contents = world_geojson(world, settings).to_string();
+    write(&path, contents).map_err(ExportError::Io)?;
+    Ok(path)
+}
+
+/// A feature collection of the coastlines, the lakes and each biome.
+pub fn world_geojson(world: &World, settings: &Settings) -> Value {
+    let position = |(x, y): Corner| {
+        let (lon, lat) = position_to_lonlat(settings, x as f64 / 2., y as f64 / 3.);
+        let round = |value: f64| (value * 1e6).round() / 1e6;
+        json!([round(lon.clamp(-180., 180.)), round(lat.clamp(-90., 90.))])
+    };
+    let positions = |line: &[Corner]| line.iter().copied().map(position).collect::<Vec<_>>();
+
+    let is_land = |index: usize| world.elevation[index] >= SEA_LEVEL;
+    let coastlines: Vec<_> = trace(&boundary(world, is_land, false))
+        .iter()
+        .map(|line| positions(line))
+        .collect();
+    let mut features = vec![json!({
+        "type": "Feature",
+        "properties": { "kind": "coastline" },
+        "geometry": { "type": "MultiLineString", "coordinates": coastlines },
+    })];
+
+    let lakes = lakes(world, settings);
+    let lake_polygons: Vec<_> = polygons(trace(&boundary(world, |index| lakes[index], true)))
+        .iter()
+        .map(|rings| rings.iter().map(|ring| positions(ring)).collect::<Vec<_>>())
+        .collect();
+    if !lake_polygons.is_empty() {
+        features.push(json!({
+            "type": "Feature",
+            "properties": { "kind": "lake" },
+            "geometry": { "type": "MultiPolygon", "coordinates": lake_polygons },
+        }));
+    }
+
+    for (id, name) in world.biome_names.iter().enumerate() {
+        let in_biome = |index: usize| world.biome[index] == Some(id as u16);
+        let polygons: Vec<_> = polygons(trace(&boundary(world, in_biome, true)))
+            .iter()
+            .map(|rings| rings.iter().map(|ring| positions(ring)).collect::<Vec<_>>())
+            .collect();
+        if polygons.is_empty() {
+            continue;
+        }
+        features.push(json!({
+            "type": "Feature",
+            "properties": { "kind": "biome", "name": name },
+            "geometry": { "type": "MultiPolygon", "coordinates": polygons },
+        }));
+    }
+
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+/// Water tiles not connected to the edges of the map, the rest of the water
+/// being the ocean.
+fn lakes(world: &World, settings: &Settings) -> Vec<bool> {
+    let map_size = TilemapSize {
+        x: world.width,
+        y: world.height,
+    };
+    let mut lakes: Vec<bool> = world
+        .elevation
+        .iter()
+        .map(|&elevation| elevation < SEA_LEVEL)
+        .collect();
+
+    for index in 0..lakes.len() {
+        let tile_pos = world.tile_pos(index);
+        let on_edge = hex::neighbours(&tile_pos, &map_size, settings.wrap).count() < 6;
+        if !on_edge || !lakes[index] {
+            continue;
+        }
+        let ocean = hex::flood_fill(&tile_pos, &map_size, settings.wrap, |tile_pos| {
+            lakes[world.index(tile_pos)]
+        });
+        for tile_pos in ocean {
+            lakes[world.index(&tile_pos)] = false;
+        }
+    }
+
+    lakes
+}
+
+/// Edges between the region and the other tiles, oriented with the region on
+/// their left. Three tiles meet at each corner, so a corner starts at most one
+/// edge and the edges are keyed by their start.
+fn boundary(
+    world: &World,
+    in_region: impl Fn(usize) -> bool,
+    map_edges: bool,
+) -> BTreeMap<Corner, Corner> {
+    let mut edges = BTreeMap::new();
+    let map_size = TilemapSize {
+        x: world.width,
+        y: world.height,
+    };
+
+    for index in (0..world.elevation.len()).filter(|&index| in_region(index)) {
+        let tile_pos = world.tile_pos(index);
+        let (x, y) = (tile_pos.x as i64, tile_pos.y as i64);
+        let centre = (2 * x + (y % 2 == 0) as i64, 3 * y);
+        let corner = |i: usize| (centre.0 + CORNERS[i % 6].0, centre.1 + CORNERS[i % 6].1);
+
+        for edge in 0..6 {
+            let is_boundary = match hex::neighbour(&tile_pos, edge, &map_size, false) {
+                Some(neighbour) => !in_region(world.index(&neighbour)),
+                None => map_edges,
+            };
+            if is_boundary {
+                edges.insert(corner(edge), corner(edge + 1));
+            }
+        }
+    }
+
+    edges
+}
+
+/// Chains edges into lines, rings end with their first corner.
+fn trace(edges: &BTreeMap<Corner, Corner>) -> Vec<Vec<Corner>> {
+    let mut remaining = edges.clone();
+    let mut lines = Vec::new();
+
+    let ends: BTreeSet<_> = edges.values().collect();
+    let mut starts: Vec<Corner> = edges
+        .keys()
+        .filter(|start| !ends.contains(start))
+        .copied()
+        .collect();
+    starts.extend(edges.keys().copied());
+
+    for start in starts {
+        if !remaining.contains_key(&start) {
+            continue;
+        }
+        let mut line = vec![start];
+        let mut corner = start;
+        while let Some(next) = remaining.remove(&corner) {
+            line.push(next);
+            corner = next;
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Groups rings into polygons: counterclockwise rings are exteriors and the
+/// clockwise ones are holes of the smallest exterior around them.
+fn polygons(rings: Vec<Vec<Corner>>) -> Vec<Vec<Vec<Corner>>> {
+    let (exteriors, holes): (Vec<_>, Vec<_>) = rings.into_iter().partition(|ring| area(ring) > 0);
+    let mut polygons: Vec<_> = exteriors.into_iter().map(|ring| vec![ring]).collect();
+
+    for hole in holes {
+        let around = polygons
+            .iter_mut()
+            .filter(|polygon| contains(&polygon[0], hole[0]))
+            .min_by_key(|polygon| area(&polygon[0]));
+        if let Some(polygon) = around {
+            polygon.push(hole);
+        }
+    }
+
+    polygons
+}
+
+/// Twice the signed area, positive for counterclockwise rings.
+fn area(ring: &[Corner]) -> i64 {
+    ring.windows(2)
+        .map(|pair| pair[0].0 * pair[1].1 - pair[1].0 * pair[0].1)
+        .sum()
+}
+
+fn contains(ring: &[Corner], (x, y): Corner) -> bool {
+    let mut inside = false;
+    for pair in ring.windows(2) {
+        let ((x1, y1), (x2, y2)) = (pair[0], pair[1]);
+        if (y1 > y) != (y2 > y) && ((x - x1) * (y2 - y1) - (x2 - x1) * (y - y1)) * (y2 - y1) < 0 {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_tilemap::tiles::TilePos;
+    use rstest::rstest;
+    use serde_json::Value;
+
+    use crate::worlds::map::generator::world::World;
+    use crate::worlds::settings::Settings;
+
+    use super::world_geojson;
+
+    /// A land tile in the middle of the ocean.
+    fn island() -> (World, Settings) {
+        let settings = Settings {
+            width: 5,
+            height: 5,
+            ..Settings::from_seed(0)
+        };
+        let count = 25;
+        let mut world = World {
+            width: 5,
+            height: 5,
+            elevation: vec![-1.; count],
+            temperature: vec![0.; count],
+            biome: vec![Some(1); count],
+            biome_names: vec!["Land".to_string(), "Ocean".to_string()],
+        };
+        let index = world.index(&TilePos { x: 2, y: 2 });
+        world.elevation[index] = 1.;
+        world.biome[index] = Some(0);
+        (world, settings)
+    }
+
+    fn feature<'a>(geojson: &'a Value, name: &str) -> &'a Value {
+        geojson["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|feature| feature["properties"]["name"] == name)
+            .unwrap()
+    }
+
+    #[rstest]
+    fn test_coastline_is_closed_around_island() {
+        let (world, settings) = island();
+        let geojson = world_geojson(&world, &settings);
+
+        let coastlines = geojson["features"][0]["geometry"]["coordinates"]
+            .as_array()
+            .unwrap();
+        assert_eq!(coastlines.len(), 1);
+        let coastline = coastlines[0].as_array().unwrap();
+        assert_eq!(coastline.len(), 7);
+        assert_eq!(coastline.first(), coastline.last());
+    }
+
+    #[rstest]
+    fn test_biome_polygons_with_holes() {
+        let (world, settings) = island();
+        let geojson = world_geojson(&world, &settings);
+
+        let land = &feature(&geojson, "Land")["geometry"]["coordinates"];
+        assert_eq!(land.as_array().unwrap().len(), 1);
+        assert_eq!(land[0].as_array().unwrap().len(), 1);
+
+        let ocean = &feature(&geojson, "Ocean")["geometry"]["coordinates"];
+        assert_eq!(ocean.as_array().unwrap().len(), 1);
+        assert_eq!(
+            ocean[0].as_array().unwrap().len(),
+            2,
+            "the island is a hole"
+        );
+        assert_eq!(ocean[0][1].as_array().unwrap().len(), 7);
+    }
+
+    #[rstest]
+    fn test_lakes() {
+        // Only the ocean around the island, then water in the middle of land
+        let (mut world, settings) = island();
+        let kinds = |world: &World| -> Vec<String> {
+            world_geojson(world, &settings)["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|feature| feature["properties"]["kind"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert!(!kinds(&world).contains(&"lake".to_string()));
+
+        for elevation in world.elevation.iter_mut() {
+            *elevation = -*elevation;
+        }
+        let geojson = world_geojson(&world, &settings);
+        let lake = geojson["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|feature| feature["properties"]["kind"] == "lake")
+            .unwrap();
+        let polygons = lake["geometry"]["coordinates"].as_array().unwrap();
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0][0].as_array().unwrap().len(), 7);
+    }
+
+    #[rstest]
+    fn test_coastlines_stop_at_map_edges() {
+        let (mut world, settings) = island();
+        for x in 0..world.width {
+            let index = world.index(&TilePos { x, y: 0 });
+            world.elevation[index] = 1.;
+        }
+        let geojson = world_geojson(&world, &settings);
+
+        let coastlines = geojson["features"][0]["geometry"]["coordinates"]
+            .as_array()
+            .unwrap();
+        assert_eq!(coastlines.len(), 2);
+        let open = coastlines
+            .iter()
+            .map(|line| line.as_array().unwrap())
+            .find(|line| line.first() != line.last())
+            .unwrap();
+        // Land on the left, from the east edge of the map to the west edge
+        assert_eq!(open[0][0], 180.);
+        assert_eq!(open[open.len() - 1][0], -180.);
+    }
+}
@@ -0,0 +1,525 @@
+//! Tiled maps (TMX) of the biomes. Tiled has no per-tile properties on tile
+//! layers, the elevation and temperature are hidden layers of grey levels whose
+//! tiles carry the value of their level in a property, and exactly in raw
+//! layers next to the map, which names them in `file` properties.
+
+use std::{
+    fmt,
+    fs::{read, read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use bevy_ecs_tilemap::tiles::TilePos;
+use image::{Rgba, RgbaImage};
+
+use crate::worlds::map::generator::{biome::BiomeId, world::World};
+use crate::worlds::settings::{Settings, MIN_MAP_SIZE};
+
+use super::ExportError;
+
+/// Tiled map written and imported from the "World" window.
+pub const TILED_MAP_FILE: &str = "map.tmx";
+
+const TILESET_FILE: &str = "biomes.png";
+const BIOME_LAYER: &str = "biomes";
+/// Map properties naming the raw layers, and their files.
+const RAW_LAYERS: [(&str, &str); 2] = [
+    ("elevation", "map_elevation.bin"),
+    ("temperature", "map_temperature.bin"),
+];
+/// Grey levels of the elevation and temperature layers.
+const LEVELS: u32 = 16;
+/// Flip and rotation bits of the gids.
+const GID_FLAGS: u32 = 0xf000_0000;
+
+/// Writes the map, its tilesets, one hexagon per biome and [`LEVELS`] grey
+/// hexagons for the elevation and the temperature, and its raw layers
+/// (little-endian `f64` row by row from the bottom, like the ones of
+/// [`export_world`](super::export_world)) to a directory.
+///
+/// Tiled rows go down while the tilemap rows go up, the first row in Tiled is
+/// the last of the tilemap and the stagger index follows the parity of the
+/// height so that the even rows of the tilemap are the shifted ones.
+pub fn export_tiled(
+    world: &World,
+    settings: &Settings,
+    biome_colors: &[[u8; 3]],
+    dir: &Path,
+) -> Result<Vec<PathBuf>, ExportError> {
+    let tile_width = settings.tile_size.x.round().max(1.) as u32;
+    let tile_height = settings.tile_size.y.round().max(1.) as u32;
+    let stagger_index = if world.height % 2 == 1 { "even" } else { "odd" };
+    let mut written = Vec::new();
+
+    let mut tmx = String::new();
+    tmx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    tmx.push_str(&format!(
+        "<map version=\"1.10\" orientation=\"hexagonal\" renderorder=\"right-down\" \
+         width=\"{}\" height=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" hexsidelength=\"{}\" \
+         staggeraxis=\"y\" staggerindex=\"{}\" infinite=\"0\" nextlayerid=\"4\" nextobjectid=\"1\">\n",
+        world.width,
+        world.height,
+        tile_width,
+        tile_height,
+        tile_height / 2,
+        stagger_index
+    ));
+
+    tmx.push_str(" <properties>\n");
+    for ((name, file), values) in RAW_LAYERS
+        .iter()
+        .zip([&world.elevation, &world.temperature])
+    {
+        let path = dir.join(file);
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        write(&path, bytes).map_err(ExportError::Io)?;
+        written.push(path);
+        tmx.push_str(&format!(
+            "  <property name=\"{}\" type=\"file\" value=\"{}\"/>\n",
+            name, file
+        ));
+    }
+    tmx.push_str(" </properties>\n");
+
+    if !world.biome_names.is_empty() {
+        let count = world.biome_names.len() as u32;
+        let path = dir.join(TILESET_FILE);
+        tileset_image(biome_colors, tile_width, tile_height)
+            .save(&path)
+            .map_err(ExportError::Image)?;
+        written.push(path);
+
+        tmx.push_str(&format!(
+            " <tileset firstgid=\"1\" name=\"biomes\" tilewidth=\"{}\" tileheight=\"{}\" \
+             tilecount=\"{}\" columns=\"{}\">\n",
+            tile_width, tile_height, count, count
+        ));
+        tmx.push_str(&format!(
+            "  <image source=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+            TILESET_FILE,
+            tile_width * count,
+            tile_height
+        ));
+        for (id, name) in world.biome_names.iter().enumerate() {
+            tmx.push_str(&format!(
+                "  <tile id=\"{}\">\n   <properties>\n    <property name=\"biome\" value=\"{}\"/>\n   </properties>\n  </tile>\n",
+                id,
+                escape(name)
+            ));
+        }
+        tmx.push_str(" </tileset>\n");
+    }
+
+    // Gids of the level tiles follow the biome tiles, then the elevation ones
+    let mut levels = Vec::new();
+    let mut first_gid = world.biome_names.len() as u32 + 1;
+    for ((name, _), values) in RAW_LAYERS
+        .iter()
+        .zip([&world.elevation, &world.temperature])
+    {
+        let (min, max) = values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            });
+        let step = if max > min {
+            (max - min) / LEVELS as f64
+        } else {
+            1.
+        };
+        let file = format!("{}.png", name);
+        let path = dir.join(&file);
+        let greys: Vec<_> = (0..LEVELS)
+            .map(|level| [(level * 255 / (LEVELS - 1)) as u8; 3])
+            .collect();
+        tileset_image(&greys, tile_width, tile_height)
+            .save(&path)
+            .map_err(ExportError::Image)?;
+        written.push(path);
+
+        tmx.push_str(&format!(
+            " <tileset firstgid=\"{}\" name=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" \
+             tilecount=\"{}\" columns=\"{}\">\n",
+            first_gid, name, tile_width, tile_height, LEVELS, LEVELS
+        ));
+        tmx.push_str(&format!(
+            "  <image source=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+            file,
+            tile_width * LEVELS,
+            tile_height
+        ));
+        for level in 0..LEVELS {
+            tmx.push_str(&format!(
+                "  <tile id=\"{}\">\n   <properties>\n    <property name=\"{}\" type=\"float\" value=\"{}\"/>\n   </properties>\n  </tile>\n",
+                level,
+                name,
+                min + (level as f64 + 0.5) * step
+            ));
+        }
+        tmx.push_str(" </tileset>\n");
+
+        let gids: Vec<_> = values
+            .iter()
+            .map(|value| {
+                let level = (((value - min) / step) as u32).min(LEVELS - 1);
+                first_gid + level
+            })
+            .collect();
+        levels.push((*name, gids));
+        first_gid += LEVELS;
+    }
+
+    let biomes: Vec<_> = world
+        .biome
+        .iter()
+        .map(|id| id.map_or(0, |id| id as u32 + 1))
+        .collect();
+    tmx.push_str(&tiled_layer(world, 1, BIOME_LAYER, true, &biomes));
+    for (id, (name, gids)) in levels.iter().enumerate() {
+        tmx.push_str(&tiled_layer(world, id as u32 + 2, name, false, gids));
+    }
+    tmx.push_str("</map>\n");
+
+    let path = dir.join(TILED_MAP_FILE);
+    write(&path, tmx).map_err(ExportError::Io)?;
+    written.push(path);
+
+    Ok(written)
+}
+
+#[derive(Debug)]
+pub enum ImportTiledError {
+    Io(std::io::Error),
+    Xml(roxmltree::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ImportTiledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportTiledError::Io(e) => write!(f, "File read error: {}", e),
+            ImportTiledError::Xml(e) => write!(f, "XML parse error: {}", e),
+            ImportTiledError::Invalid(e) => write!(f, "Invalid Tiled map: {}", e),
+        }
+    }
+}
+
+/// Reads back a map written by [`export_tiled`], possibly edited in Tiled.
+///
+/// The map must keep the CSV encoding and the properties naming its raw layers,
+/// biomes are named after the `biome` property of the tiles of the `biomes`
+/// tileset. The grey levels of the elevation and temperature are not read back,
+/// the raw layers are.
+pub fn import_tiled(path: &Path) -> Result<World, ImportTiledError> {
+    let contents = read_to_string(path).map_err(ImportTiledError::Io)?;
+    parse_tiled(&contents, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Parses a map whose raw layers are in `dir`.
+pub fn parse_tiled(contents: &str, dir: &Path) -> Result<World, ImportTiledError> {
+    let invalid = |message: &str| ImportTiledError::Invalid(message.to_string());
+    let document = roxmltree::Document::parse(contents).map_err(ImportTiledError::Xml)?;
+    let map = document.root_element();
+    let number = |name: &str| -> Result<u32, ImportTiledError> {
+        map.attribute(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| invalid(&format!("missing map `{}`", name)))
+    };
+    let (width, height) = (number("width")?, number("height")?);
+    if width < MIN_MAP_SIZE || height < MIN_MAP_SIZE {
+        return Err(invalid(&format!(
+            "the map must be at least {} tiles wide and high",
+            MIN_MAP_SIZE
+        )));
+    }
+    if map.attribute("orientation") != Some("hexagonal")
+        || map.attribute("staggeraxis") != Some("y")
+    {
+        return Err(invalid("the map is not staggered by rows of hexagons"));
+    }
+
+    let mut biome_names = Vec::new();
+    let mut first_gid = 1;
+    if let Some(tileset) = map
+        .children()
+        .find(|node| node.has_tag_name("tileset") && node.attribute("name") == Some(BIOME_LAYER))
+    {
+        first_gid = tileset
+            .attribute("firstgid")
+            .and_then(|gid| gid.parse().ok())
+            .unwrap_or(1);
+        for tile in tileset.children().filter(|node| node.has_tag_name("tile")) {
+            let id: usize = tile
+                .attribute("id")
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| invalid("tile without id"))?;
+            let name = property(tile, "biome").ok_or_else(|| invalid("tile without biome"))?;
+            if biome_names.len() <= id {
+                biome_names.resize(id + 1, String::new());
+            }
+            biome_names[id] = name.to_string();
+        }
+    }
+
+    let layer = map
+        .children()
+        .find(|node| node.has_tag_name("layer") && node.attribute("name") == Some(BIOME_LAYER))
+        .ok_or_else(|| invalid("missing biomes layer"))?;
+    let data = layer
+        .children()
+        .find(|node| node.has_tag_name("data"))
+        .filter(|data| data.attribute("encoding") == Some("csv"))
+        .ok_or_else(|| invalid("the biomes layer is not CSV encoded"))?;
+
+    let count = width as usize * height as usize;
+    let gids = data
+        .text()
+        .unwrap_or_default()
+        .split(',')
+        .map(|gid| gid.trim().parse::<u32>().map(|gid| gid & !GID_FLAGS))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid("invalid `data` values"))?;
+    if gids.len() != count {
+        return Err(invalid(&format!("expected {} `data` values", count)));
+    }
+
+    let [elevation, temperature] = RAW_LAYERS.map(|(name, _)| {
+        let file =
+            property(map, name).ok_or_else(|| invalid(&format!("missing `{}` property", name)))?;
+        let bytes = read(dir.join(file)).map_err(ImportTiledError::Io)?;
+        if bytes.len() != count * 8 {
+            return Err(invalid(&format!("expected {} `{}` values", count, name)));
+        }
+        Ok(bytes
+            .chunks_exact(8)
+            .map(|value| f64::from_le_bytes(value.try_into().unwrap()))
+            .collect::<Vec<_>>())
+    });
+
+    let mut world = World {
+        width,
+        height,
+        elevation: elevation?,
+        temperature: temperature?,
+        biome: vec![None; count],
+        biome_names,
+    };
+    let indices: Vec<usize> = tiled_rows(&world).flatten().collect();
+    for (tiled_index, index) in indices.into_iter().enumerate() {
+        let gid = gids[tiled_index] as usize;
+        world.biome[index] = match gid.checked_sub(first_gid) {
+            _ if gid == 0 => None,
+            Some(id) if id < world.biome_names.len() => Some(id as BiomeId),
+            _ => return Err(invalid(&format!("unknown tile {}", gid))),
+        };
+    }
+
+    Ok(world)
+}
+
+/// Tile layer of gids indexed like the world, CSV encoded.
+fn tiled_layer(world: &World, id: u32, name: &str, visible: bool, gids: &[u32]) -> String {
+    let rows = tiled_rows(world)
+        .map(|row| {
+            row.map(|index| gids[index].to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    format!(
+        " <layer id=\"{}\" name=\"{}\" width=\"{}\" height=\"{}\"{}>\n  <data encoding=\"csv\">\n{}\n</data>\n </layer>\n",
+        id,
+        name,
+        world.width,
+        world.height,
+        if visible { "" } else { " visible=\"0\"" },
+        rows
+    )
+}
+
+/// World indices of the tiles, row by row in the order of Tiled.
+fn tiled_rows(world: &World) -> impl Iterator<Item = impl Iterator<Item = usize> + '_> + '_ {
+    (0..world.height)
+        .rev()
+        .map(move |y| (0..world.width).map(move |x| world.index(&TilePos { x, y })))
+}
+
+fn property<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|node| node.has_tag_name("properties"))?
+        .children()
+        .find(|node| node.has_tag_name("property") && node.attribute("name") == Some(name))
+        .and_then(|property| property.attribute("value").or_else(|| property.text()))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Biome hexagons side by side, transparent around them.
+fn tileset_image(biome_colors: &[[u8; 3]], tile_width: u32, tile_height: u32) -> RgbaImage {
+    let (half_width, half_height) = (tile_width as f32 / 2., tile_height as f32 / 2.);
+    RgbaImage::from_fn(
+        tile_width * biome_colors.len() as u32,
+        tile_height,
+        |x, y| {
+            let dx = ((x % tile_width) as f32 + 0.5 - half_width).abs();
+            let dy = (y as f32 + 0.5 - half_height).abs();
+            if dy <= half_height - dx * half_height / (2. * half_width) {
+                let [r, g, b] = biome_colors[(x / tile_width) as usize];
+                Rgba([r, g, b, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy_ecs_tilemap::tiles::TilePos;
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use crate::worlds::map::biomes::{load_biomes, BIOMES_PATH};
+    use crate::worlds::map::generator::world::WorldGenerator;
+    use crate::worlds::settings::Settings;
+
+    use crate::worlds::map::generator::world::World;
+
+    use super::{export_tiled, import_tiled, parse_tiled, property, LEVELS, TILED_MAP_FILE};
+
+    fn world(height: u32) -> (Settings, World) {
+        let settings = Settings {
+            width: 12,
+            height,
+            ..Settings::from_seed(1234)
+        };
+        let biomes = load_biomes(Path::new(BIOMES_PATH)).unwrap();
+        let world = WorldGenerator::new(settings.clone())
+            .with_biomes(biomes.into_values())
+            .generate()
+            .unwrap();
+        (settings, world)
+    }
+
+    #[rstest]
+    #[case::odd_height(7, "staggerindex=\"even\"")]
+    #[case::even_height(8, "staggerindex=\"odd\"")]
+    fn test_round_trip(#[case] height: u32, #[case] stagger_index: &str) {
+        let (settings, world) = world(height);
+        let dir = tempdir().unwrap();
+
+        export_tiled(&world, &settings, &[[0, 0, 255], [0, 255, 0]], dir.path()).unwrap();
+
+        let path = dir.path().join(TILED_MAP_FILE);
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains(stagger_index));
+        assert_eq!(
+            std::fs::read(dir.path().join("map_elevation.bin"))
+                .unwrap()
+                .len(),
+            world.elevation.len() * 8
+        );
+        assert_eq!(import_tiled(&path).unwrap(), world);
+    }
+
+    #[rstest]
+    fn test_level_layers() {
+        let (settings, world) = world(4);
+        let dir = tempdir().unwrap();
+
+        export_tiled(&world, &settings, &[[0, 0, 255], [0, 255, 0]], dir.path()).unwrap();
+
+        let tmx = std::fs::read_to_string(dir.path().join(TILED_MAP_FILE)).unwrap();
+        let document = roxmltree::Document::parse(&tmx).unwrap();
+        let map = document.root_element();
+        for name in ["elevation", "temperature"] {
+            let tileset = map
+                .children()
+                .find(|node| node.attribute("name") == Some(name) && node.has_tag_name("tileset"))
+                .unwrap();
+            let first_gid: u32 = tileset.attribute("firstgid").unwrap().parse().unwrap();
+            let layer = map
+                .children()
+                .find(|node| node.attribute("name") == Some(name) && node.has_tag_name("layer"))
+                .unwrap();
+            assert_eq!(layer.attribute("visible"), Some("0"));
+            assert!(dir.path().join(format!("{}.png", name)).exists());
+
+            // The first Tiled tile is the last row of the world
+            let values = if name == "elevation" {
+                &world.elevation
+            } else {
+                &world.temperature
+            };
+            let value = values[world.index(&TilePos { x: 0, y: 3 })];
+            let gid: u32 = layer
+                .children()
+                .find(|node| node.has_tag_name("data"))
+                .and_then(|data| data.text())
+                .and_then(|text| text.trim().split(',').next())
+                .and_then(|gid| gid.parse().ok())
+                .unwrap();
+            let tile = tileset
+                .children()
+                .find(|tile| tile.attribute("id") == Some(&(gid - first_gid).to_string()))
+                .unwrap();
+            let level: f64 = property(tile, name).unwrap().parse().unwrap();
+            let (min, max) = values
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+                    (min.min(value), max.max(value))
+                });
+            assert!((level - value).abs() <= (max - min) / LEVELS as f64 / 2. + 1e-9);
+        }
+    }
+
+    #[rstest]
+    fn test_too_small_map() {
+        let (settings, world) = world(4);
+        let dir = tempdir().unwrap();
+        export_tiled(&world, &settings, &[[0, 0, 255], [0, 255, 0]], dir.path()).unwrap();
+        let tmx = std::fs::read_to_string(dir.path().join(TILED_MAP_FILE)).unwrap();
+
+        let tmx = tmx.replacen("height=\"4\"", "height=\"1\"", 1);
+
+        assert_eq!(
+            parse_tiled(&tmx, dir.path()).unwrap_err().to_string(),
+            "Invalid Tiled map: the map must be at least 2 tiles wide and high"
+        );
+    }
+
+    #[rstest]
+    fn test_flipped_tiles() {
+        let (settings, world) = world(4);
+        let dir = tempdir().unwrap();
+        export_tiled(&world, &settings, &[[0, 0, 255], [0, 255, 0]], dir.path()).unwrap();
+        let tmx = std::fs::read_to_string(dir.path().join(TILED_MAP_FILE)).unwrap();
+
+        // The first tile flipped horizontally in Tiled is still its biome
+        let (head, data) = tmx.split_once("<data encoding=\"csv\">\n").unwrap();
+        let (gid, tail) = data.split_once(',').unwrap();
+        let flipped = gid.parse::<u32>().unwrap() | 0x8000_0000;
+        let tmx = format!("{}<data encoding=\"csv\">\n{},{}", head, flipped, tail);
+        assert_eq!(parse_tiled(&tmx, dir.path()).unwrap(), world);
+
+        // Gids are integers
+        let tmx = format!("{}<data encoding=\"csv\">\n{}.5,{}", head, gid, tail);
+        assert_eq!(
+            parse_tiled(&tmx, dir.path()).unwrap_err().to_string(),
+            "Invalid Tiled map: invalid `data` values"
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Hexagonal grid math over the `RowEven` tilemap.
+//!
+//! Rows go up and the even rows are shifted half a tile to the right. Axial
+//! coordinates keep `r` as the row and make `q` grow to the east, so the
+//! six directions are (counterclockwise from the east) `(1, 0)`, `(0, 1)`,
+//! `(-1, 1)`, `(-1, 0)`, `(0, -1)` and `(1, -1)`.
+
+use std::collections::{HashSet, VecDeque};
+
+use bevy_ecs_tilemap::{map::TilemapSize, tiles::TilePos};
+
+/// Neighbour offsets, counterclockwise from the east.
+pub const DIRECTIONS: [Axial; 6] = [
+    Axial { q: 1, r: 0 },
+    Axial { q: 0, r: 1 },
+    Axial { q: -1, r: 1 },
+    Axial { q: -1, r: 0 },
+    Axial { q: 0, r: -1 },
+    Axial { q: 1, r: -1 },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Axial {
+    pub q: i32,
+    pub r: i32,
+}
+
+/// Axial coordinates with `s = -q - r`, handy for rounding and symmetries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Cube {
+    pub q: i32,
+    pub r: i32,
+    pub s: i32,
+}
+
+impl Axial {
+    pub fn new(q: i32, r: i32) -> Self {
+        Self { q, r }
+    }
+
+    pub fn from_tile_pos(tile_pos: &TilePos) -> Self {
+        Self::from_offset(tile_pos.x as i32, tile_pos.y as i32)
+    }
+
+    /// From a column and a row, which may be outside the map.
+    pub fn from_offset(x: i32, y: i32) -> Self {
+        Self {
+            q: x - (y + 1).div_euclid(2),
+            r: y,
+        }
+    }
+
+    pub fn to_offset(self) -> (i32, i32) {
+        (self.q + (self.r + 1).div_euclid(2), self.r)
+    }
+
+    /// The tile at these coordinates, `None` outside the map.
+    pub fn to_tile_pos(self, map_size: &TilemapSize) -> Option<TilePos> {
+        let (x, y) = self.to_offset();
+        let in_map = (0..map_size.x as i32).contains(&x) && (0..map_size.y as i32).contains(&y);
+        in_map.then_some(TilePos {
+            x: x as u32,
+            y: y as u32,
+        })
+    }
+
+    /// Same as [`Axial::to_tile_pos`] on a map whose east and west edges meet.
+    pub fn to_tile_pos_wrapped(self, map_size: &TilemapSize) -> Option<TilePos> {
+        if map_size.x == 0 {
+            return None;
+        }
+        let (x, y) = self.to_offset();
+        let x = x.rem_euclid(map_size.x as i32);
+        Axial::from_offset(x, y).to_tile_pos(map_size)
+    }
+
+    pub fn to_cube(self) -> Cube {
+        Cube {
+            q: self.q,
+            r: self.r,
+            s: -self.q - self.r,
+        }
+    }
+
+    pub fn neighbour(self, direction: usize) -> Self {
+        self + DIRECTIONS[direction % 6]
+    }
+
+    pub fn neighbours(self) -> [Self; 6] {
+        DIRECTIONS.map(|direction| self + direction)
+    }
+
+    pub fn distance(self, other: Self) -> u32 {
+        let Cube { q, r, s } = (self - other).to_cube();
+        q.unsigned_abs().max(r.unsigned_abs()).max(s.unsigned_abs())
+    }
+
+    /// Distance on a map of this width whose east and west edges meet.
+    pub fn wrapped_distance(self, other: Self, width: u32) -> u32 {
+        let width = width as i32;
+        [-width, 0, width]
+            .into_iter()
+            .map(|shift| self.distance(other + Axial::new(shift, 0)))
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Hexes at exactly `radius` steps, counterclockwise from the south-west corner.
+    pub fn ring(self, radius: u32) -> Vec<Self> {
+        if radius == 0 {
+            return vec![self];
+        }
+
+        let mut hex = self + DIRECTIONS[4] * radius as i32;
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        for direction in DIRECTIONS {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex + direction;
+            }
+        }
+        ring
+    }
+
+    /// Hexes up to `radius` steps, ring after ring from the centre.
+    pub fn spiral(self, radius: u32) -> Vec<Self> {
+        (0..=radius).flat_map(|radius| self.ring(radius)).collect()
+    }
+
+    /// Hexes crossed by the segment between both centres, ends included.
+    pub fn line(self, other: Self) -> Vec<Self> {
+        let distance = self.distance(other);
+        // Nudged off the edges so that ties always round the same way
+        let (a, b) = (self.to_cube(), other.to_cube());
+        let a = [a.q as f64 + 1e-6, a.r as f64 + 2e-6, a.s as f64 - 3e-6];
+        let b = [b.q as f64 + 1e-6, b.r as f64 + 2e-6, b.s as f64 - 3e-6];
+
+        (0..=distance)
+            .map(|step| {
+                let t = if distance == 0 {
+                    0.
+                } else {
+                    step as f64 / distance as f64
+                };
+                let [q, r, s] = [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+                Cube::round(q, r, s).into()
+            })
+            .collect()
+    }
+}
+
+impl Cube {
+    /// Nearest hex of fractional coordinates.
+    pub fn round(q: f64, r: f64, s: f64) -> Self {
+        let (mut rq, mut rr, mut rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        } else {
+            rs = -rq - rr;
+        }
+        Self {
+            q: rq as i32,
+            r: rr as i32,
+            s: rs as i32,
+        }
+    }
+}
+
+impl From<Cube> for Axial {
+    fn from(cube: Cube) -> Self {
+        Self {
+            q: cube.q,
+            r: cube.r,
+        }
+    }
+}
+
+impl From<Axial> for Cube {
+    fn from(axial: Axial) -> Self {
+        axial.to_cube()
+    }
+}
+
+impl std::ops::Add for Axial {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl std::ops::Sub for Axial {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+impl std::ops::Mul<i32> for Axial {
+    type Output = Self;
+
+    fn mul(self, factor: i32) -> Self {
+        Self::new(self.q * factor, self.r * factor)
+    }
+}
+
+/// Neighbours of a tile inside the map, counterclockwise from the east.
+///
+/// When `wrap` is set, the east and west edges of the map are neighbours.
+pub fn neighbours(
+    tile_pos: &TilePos,
+    map_size: &TilemapSize,
+    wrap: bool,
+) -> impl Iterator<Item = TilePos> {
+    let tile_pos = *tile_pos;
+    let map_size = *map_size;
+    (0..DIRECTIONS.len())
+        .filter_map(move |direction| neighbour(&tile_pos, direction, &map_size, wrap))
+}
+
+/// Neighbour of a tile in a direction (see [`DIRECTIONS`]), `None` outside the map.
+pub fn neighbour(
+    tile_pos: &TilePos,
+    direction: usize,
+    map_size: &TilemapSize,
+    wrap: bool,
+) -> Option<TilePos> {
+    let hex = Axial::from_tile_pos(tile_pos).neighbour(direction);
+    if wrap {
+        hex.to_tile_pos_wrapped(map_size)
+    } else {
+        hex.to_tile_pos(map_size)
+    }
+}
+
+/// Connected tiles for which `in_region` holds, from `start` in breadth-first order.
+pub fn flood_fill(
+    start: &TilePos,
+    map_size: &TilemapSize,
+    wrap: bool,
+    mut in_region: impl FnMut(&TilePos) -> bool,
+) -> Vec<TilePos> {
+    let mut region = Vec::new();
+    if !in_region(start) {
+        return region;
+    }
+
+    let mut visited = HashSet::from([*start]);
+    let mut queue = VecDeque::from([*start]);
+    while let Some(tile_pos) = queue.pop_front() {
+        region.push(tile_pos);
+        for neighbour in neighbours(&tile_pos, map_size, wrap) {
+            if visited.insert(neighbour) && in_region(&neighbour) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    region
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bevy_ecs_tilemap::{map::TilemapSize, tiles::TilePos};
+    use proptest::prelude::*;
+    use rstest::rstest;
+
+    use super::{flood_fill, neighbours, Axial, Cube, DIRECTIONS};
+
+    const MAP_SIZE: TilemapSize = TilemapSize { x: 7, y: 6 };
+
+    fn axial() -> impl Strategy<Value = Axial> {
+        (-1000..1000, -1000..1000).prop_map(|(q, r)| Axial::new(q, r))
+    }
+
+    proptest! {
+        #[test]
+        fn offset_round_trip(x in -1000..1000, y in -1000..1000) {
+            prop_assert_eq!(Axial::from_offset(x, y).to_offset(), (x, y));
+        }
+
+        #[test]
+        fn cube_round_trip(hex in axial()) {
+            let cube = hex.to_cube();
+            prop_assert_eq!(cube.q + cube.r + cube.s, 0);
+            prop_assert_eq!(Axial::from(cube), hex);
+            prop_assert_eq!(Cube::round(cube.q as f64, cube.r as f64, cube.s as f64), cube);
+        }
+
+        #[test]
+        fn neighbours_are_at_distance_one(hex in axial()) {
+            let neighbours = hex.neighbours();
+            prop_assert_eq!(neighbours.iter().collect::<HashSet<_>>().len(), 6);
+            for neighbour in neighbours {
+                prop_assert_eq!(hex.distance(neighbour), 1);
+                prop_assert!(neighbour.neighbours().contains(&hex));
+            }
+        }
+
+        #[test]
+        fn distance_is_a_metric(a in axial(), b in axial(), c in axial()) {
+            prop_assert_eq!(a.distance(b), b.distance(a));
+            prop_assert_eq!(a.distance(a), 0);
+            prop_assert!(a.distance(c) <= a.distance(b) + b.distance(c));
+        }
+
+        #[test]
+        fn ring_hexes_are_at_radius(hex in axial(), radius in 0..20u32) {
+            let ring = hex.ring(radius);
+            prop_assert_eq!(ring.len(), (6 * radius).max(1) as usize);
+            prop_assert_eq!(ring.iter().collect::<HashSet<_>>().len(), ring.len());
+            for other in &ring {
+                prop_assert_eq!(hex.distance(*other), radius);
+            }
+            for pair in ring.windows(2) {
+                prop_assert_eq!(pair[0].distance(pair[1]), 1);
+            }
+        }
+
+        #[test]
+        fn spiral_covers_the_disk(hex in axial(), radius in 0..10u32) {
+            let spiral = hex.spiral(radius);
+            prop_assert_eq!(spiral.len(), (1 + 3 * radius * (radius + 1)) as usize);
+            prop_assert_eq!(spiral.iter().collect::<HashSet<_>>().len(), spiral.len());
+            prop_assert!(spiral.iter().all(|other| hex.distance(*other) <= radius));
+        }
+
+        #[test]
+        fn line_steps_between_neighbours(a in axial(), b in axial()) {
+            let line = a.line(b);
+            prop_assert_eq!(line.len(), a.distance(b) as usize + 1);
+            prop_assert_eq!(line[0], a);
+            prop_assert_eq!(line[line.len() - 1], b);
+            for pair in line.windows(2) {
+                prop_assert_eq!(pair[0].distance(pair[1]), 1);
+            }
+        }
+
+        #[test]
+        fn neighbours_stay_in_map(x in 0..MAP_SIZE.x, y in 0..MAP_SIZE.y) {
+            let tile_pos = TilePos { x, y };
+            let inside = neighbours(&tile_pos, &MAP_SIZE, false).count();
+            let on_edge = x == 0 || y == 0 || x == MAP_SIZE.x - 1 || y == MAP_SIZE.y - 1;
+            prop_assert_eq!(inside == 6, !on_edge);
+
+            let wrapped: Vec<_> = neighbours(&tile_pos, &MAP_SIZE, true).collect();
+            let on_pole = y == 0 || y == MAP_SIZE.y - 1;
+            prop_assert_eq!(wrapped.len() == 6, !on_pole);
+            for neighbour in wrapped {
+                prop_assert!(neighbours(&neighbour, &MAP_SIZE, true).any(|other| other == tile_pos));
+            }
+        }
+
+        #[test]
+        fn wrapped_distance_ignores_the_seam(a in axial(), b in axial(), width in 1..100u32) {
+            prop_assert_eq!(a.wrapped_distance(b, width), b.wrapped_distance(a, width));
+            prop_assert!(a.wrapped_distance(b, width) <= a.distance(b));
+            prop_assert_eq!(a.wrapped_distance(a + Axial::new(width as i32, 0), width), 0);
+        }
+    }
+
+    #[rstest]
+    // Even rows are shifted to the right: their neighbours above are x and x + 1
+    #[case::even_row(TilePos { x: 2, y: 2 }, [(3, 2), (3, 3), (2, 3), (1, 2), (2, 1), (3, 1)])]
+    #[case::odd_row(TilePos { x: 2, y: 3 }, [(3, 3), (2, 4), (1, 4), (1, 3), (1, 2), (2, 2)])]
+    fn test_neighbours_offsets(#[case] tile_pos: TilePos, #[case] expected: [(u32, u32); 6]) {
+        let neighbours: Vec<_> = neighbours(&tile_pos, &MAP_SIZE, false)
+            .map(|tile_pos| (tile_pos.x, tile_pos.y))
+            .collect();
+
+        assert_eq!(neighbours, expected);
+    }
+
+    #[rstest]
+    fn test_corner_neighbours() {
+        let neighbours: Vec<_> = neighbours(&TilePos { x: 0, y: 0 }, &MAP_SIZE, false).collect();
+
+        assert_eq!(
+            neighbours,
+            [
+                TilePos { x: 1, y: 0 },
+                TilePos { x: 1, y: 1 },
+                TilePos { x: 0, y: 1 }
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_wrapped_neighbours() {
+        // West of the first column is the last one, on both kinds of rows
+        let west = |y| super::neighbour(&TilePos { x: 0, y }, 3, &MAP_SIZE, true);
+
+        assert_eq!(west(2), Some(TilePos { x: 6, y: 2 }));
+        assert_eq!(west(3), Some(TilePos { x: 6, y: 3 }));
+        assert_eq!(
+            super::neighbour(&TilePos { x: 0, y: 3 }, 2, &MAP_SIZE, true),
+            Some(TilePos { x: 6, y: 4 })
+        );
+        // Nothing to wrap to on an empty map
+        let empty = TilemapSize { x: 0, y: 3 };
+        assert_eq!(Axial::new(1, 1).to_tile_pos_wrapped(&empty), None);
+    }
+
+    #[rstest]
+    fn test_flood_fill() {
+        // Everything but the column x = 3 splits the map in two
+        let not_wall = |tile_pos: &TilePos| tile_pos.x != 3;
+        let region = flood_fill(&TilePos { x: 0, y: 0 }, &MAP_SIZE, false, not_wall);
+
+        assert_eq!(region.len(), 3 * MAP_SIZE.y as usize);
+        assert!(region.iter().all(|tile_pos| tile_pos.x < 3));
+        assert_eq!(region[0], TilePos { x: 0, y: 0 });
+        assert!(flood_fill(&TilePos { x: 3, y: 0 }, &MAP_SIZE, false, not_wall).is_empty());
+
+        // Around the seam, both sides of the wall are connected
+        let region = flood_fill(&TilePos { x: 0, y: 0 }, &MAP_SIZE, true, not_wall);
+        assert_eq!(region.len(), 6 * MAP_SIZE.y as usize);
+    }
+
+    #[rstest]
+    fn test_directions_are_counterclockwise() {
+        // East, then north-east which is the same column on odd rows
+        assert_eq!(DIRECTIONS[0], Axial::new(1, 0));
+        assert_eq!(
+            (Axial::from_offset(0, 1) + DIRECTIONS[1]).to_offset(),
+            (0, 2)
+        );
+    }
+}
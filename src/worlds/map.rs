@@ -5,30 +5,45 @@ use bevy::prelude::*;
 use bevy::render::settings;
 use bevy::render::view::visibility;
 use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
-use bevy::{math::uvec2, transform::commands};
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use bevy::{
+    math::{uvec2, URect},
+    transform::commands,
+};
 use bevy_ecs_tilemap::prelude::*;
 use biomes::Biome;
-use events::{DrawMapEvent, GenerateMapEvent};
-use generator::elevation::{generate as elevation_gen, ElevationGenerator, TileElevation};
-use generator::temperature::generate as temperature_gen;
+use events::{DrawMapEvent, GenerateMapEvent, MapGenerated};
+use generator::biome::TileBiome;
+use generator::elevation::{ElevationGenerator, TileElevation};
 use generator::temperature::{TemperatureGenerator, TileTemperature};
+use generator::world::{self, Cancelled, GenerationProgress, WorldGenerator};
 use generator::MapGenerator;
+use layers::LayerGrid;
+use lod::MapLod;
 use noise::{NoiseFn, Perlin};
-use renderer::elevation::ElevationMapRenderer;
-use renderer::temperature::TemperatureMapRenderer;
 use shapes::{CircleCenteredShape, ContinentsShape, ShapeGenerator, ShapeGeneratorResource};
+use wrap::WrapCopy;
 
-use super::settings::{MapMode, Settings};
+use super::settings::{MapMode, RenderMode, Settings};
+use super::WorldsAssets;
+use biomes::Biomes;
 pub(crate) mod biomes;
-mod events;
-mod generator;
-mod renderer;
+pub(crate) mod chunks;
+pub(crate) mod events;
+pub(crate) mod generator;
+pub(crate) mod inspect;
+pub(crate) mod layers;
+mod lod;
+pub(crate) mod renderer;
 mod shapes;
+mod sprite;
+mod wrap;
 
 const MAX_PERLIN_SCALE: f64 = 100000.;
 
+/// The tilemap of the world, its wrap copy aside.
 #[derive(Component)]
-struct LastUpdate(f64);
+struct MapTilemap;
 
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
 enum MapSet {
@@ -37,41 +52,187 @@ enum MapSet {
     Render,
 }
 
+/// Steps of the generation. `Generating` and `Classifying` run on the
+/// [`AsyncComputeTaskPool`] and last as many frames as they need, the others
+/// one frame. A [`GenerateMapEvent`] cancels a running generation and restarts
+/// from `Generating`, a [`DrawMapEvent`] only goes through `Rendering`.
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
-enum MapState {
+pub enum MapState {
+    /// Nothing generated yet.
     #[default]
-    Normal,
+    Idle,
+    /// Generating the elevation and temperature.
+    Generating,
+    /// Classifying the biomes of the tiles.
+    Classifying,
+    /// Copying the world to the tiles and colouring them.
+    Rendering,
+    /// The world is generated and drawn, see [`MapGenerated`].
+    Ready,
+}
+
+/// Last generated world, copied into the layer grids, and the settings it
+/// was generated from, which the live settings may have changed since.
+#[derive(Resource, Default)]
+pub struct GeneratedWorld(pub world::World, pub Settings);
+
+/// World loaded from a save, used instead of generating one on the next
+/// generation. Set it along with the settings of the save.
+#[derive(Resource, Default)]
+pub struct PendingWorld(pub Option<world::World>);
+
+/// Generation step running in the background, polled every frame.
+#[derive(Resource, Default)]
+pub struct GenerationTask {
+    task: Option<Task<Result<world::World, Cancelled>>>,
+    pub progress: GenerationProgress,
+}
+
+impl GenerationTask {
+    /// Polls the running step, or starts it with `start`, and returns its
+    /// world once done. Cancelled steps return nothing.
+    fn poll(
+        &mut self,
+        start: impl FnOnce(GenerationProgress) -> Task<Result<world::World, Cancelled>>,
+    ) -> Option<world::World> {
+        let task = self.task.get_or_insert_with(|| {
+            self.progress = GenerationProgress::default();
+            start(self.progress.clone())
+        });
+        let world = block_on(future::poll_once(task))?;
+        self.task = None;
+        world.ok()
+    }
+
+    fn cancel(&mut self) {
+        self.progress.cancel();
+        self.task = None;
+    }
 }
 
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins(TilemapPlugin)
+    app.add_plugins((
+        TilemapPlugin,
+        biomes::plugin,
+        chunks::plugin,
+        wrap::plugin,
+        sprite::plugin,
+        lod::plugin,
+        inspect::plugin,
+        pipeline,
+    ))
+    .add_systems(Startup, setup_map);
+}
+
+/// Generation and rendering steps, driven by [`MapState`].
+fn pipeline(app: &mut App) {
+    app.init_resource::<GeneratedWorld>()
+        .init_resource::<PendingWorld>()
+        .init_resource::<GenerationTask>()
+        .init_resource::<LayerGrid<TileElevation>>()
+        .init_resource::<LayerGrid<TileTemperature>>()
+        .init_resource::<LayerGrid<TileBiome>>()
+        .init_resource::<MapLod>()
+        .add_event::<GenerateMapEvent>()
+        .add_event::<DrawMapEvent>()
+        .add_event::<MapGenerated>()
         .init_state::<MapState>()
         .configure_sets(
             Update,
             (MapSet::Prepare, MapSet::Generate, MapSet::Render).chain(),
         )
-        .add_systems(Startup, setup_map)
         .add_systems(
             Update,
             (
-                (elevation_gen, temperature_gen).in_set(MapSet::Generate),
-                (update_tiles_color).in_set(MapSet::Render),
-            )
-                .run_if(resource_changed::<Settings>),
-        );
+                (resize_map, request_map_update)
+                    .in_set(MapSet::Prepare)
+                    .run_if(resource_changed::<Settings>),
+                (
+                    generate_world.run_if(in_state(MapState::Generating)),
+                    classify_world.run_if(in_state(MapState::Classifying)),
+                )
+                    .in_set(MapSet::Generate),
+                (
+                    fill_layer_grids.run_if(in_state(MapState::Rendering)),
+                    (update_tiles_color, finish_rendering)
+                        .chain()
+                        .run_if(drawing),
+                    // Last, so that requests override the next step
+                    handle_map_events,
+                )
+                    .chain()
+                    .in_set(MapSet::Render),
+            ),
+        )
+        .add_systems(OnEnter(MapState::Ready), announce_map);
 }
 
-fn setup_map(mut commands: Commands, config: Res<Settings>, asset_server: Res<AssetServer>) {
+/// Whether the map is being or has been rendered, so that edits are drawn.
+fn drawing(state: Res<State<MapState>>) -> bool {
+    matches!(state.get(), MapState::Rendering | MapState::Ready)
+}
+
+fn setup_map(
+    mut commands: Commands,
+    config: Res<Settings>,
+    assets: Res<WorldsAssets>,
+    asset_server: Res<AssetServer>,
+) {
+    let texture_handle: Handle<Image> = asset_server.load(&assets.tile_texture);
+    spawn_tilemap(&mut commands, &config, texture_handle);
+}
+
+fn spawn_tilemap(commands: &mut Commands, config: &Settings, texture_handle: Handle<Image>) {
     let map_size = TilemapSize {
         x: config.width,
         y: config.height,
     };
 
     let tilemap_entity = commands.spawn_empty().id();
-    let texture_handle: Handle<Image> = asset_server.load("tiles_white.png");
     let coord_sys: HexCoordSystem = HexCoordSystem::RowEven;
+    let tile_storage = spawn_tiles(commands, config, tilemap_entity, map_size);
+
+    let tile_size = TilemapTileSize {
+        x: config.tile_size.x,
+        y: config.tile_size.y,
+    };
+
+    let grid_size = tile_size.into();
+    let map_type = TilemapType::Hexagon(coord_sys);
+
+    commands.entity(tilemap_entity).insert((
+        TilemapBundle {
+            grid_size,
+            map_type,
+            tile_size,
+            size: map_size,
+            storage: tile_storage,
+            texture: TilemapTexture::Single(texture_handle),
+            transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0),
+            ..default()
+        },
+        MapTilemap,
+    ));
+}
+
+/// Whether the tilemaps have tile entities: the image renderer draws the
+/// tiles itself, and streamed chunks replace the map, however large it is.
+fn has_tile_entities(settings: &Settings) -> bool {
+    settings.render_mode == RenderMode::Tilemap && !settings.streaming
+}
 
-    // Initialize tile storage with empty tiles
+/// Spawns a tile entity per tile of a tilemap, none unless
+/// [`has_tile_entities`], in which case the storage is empty rather than sized
+/// for the whole map.
+fn spawn_tiles(
+    commands: &mut Commands,
+    config: &Settings,
+    tilemap_entity: Entity,
+    map_size: TilemapSize,
+) -> TileStorage {
+    if !has_tile_entities(config) {
+        return TileStorage::empty(TilemapSize { x: 0, y: 0 });
+    }
     let mut tile_storage = TileStorage::empty(map_size);
     for x in 0..map_size.x {
         for y in 0..map_size.y {
@@ -87,63 +248,607 @@ fn setup_map(mut commands: Commands, config: Res<Settings>, asset_server: Res<As
             tile_storage.set(&tile_pos, tile_entity);
         }
     }
+    tile_storage
+}
 
+type TilemapLayout<'a> = (
+    Entity,
+    &'a TilemapSize,
+    &'a TileStorage,
+    &'a TilemapTexture,
+    &'a TilemapType,
+    &'a mut TilemapTileSize,
+    &'a mut TilemapGridSize,
+    &'a mut Transform,
+    Has<WrapCopy>,
+);
+
+/// Rebuilds the tilemap when the dimensions of the settings change, and
+/// rescales it in place when only the tile size does. The wrap copy is
+/// rebuilt by the wrap plugin, and the tiles get the world generated for the
+/// new settings. Streamed chunks and summaries are left to their plugins.
+fn resize_map(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut tilemap_query: Query<TilemapLayout, Or<(With<MapTilemap>, With<WrapCopy>)>>,
+) {
+    let map_size = TilemapSize {
+        x: settings.width,
+        y: settings.height,
+    };
     let tile_size = TilemapTileSize {
-        x: config.tile_size.x,
-        y: config.tile_size.y,
+        x: settings.tile_size.x,
+        y: settings.tile_size.y,
     };
 
-    let grid_size = tile_size.into();
-    let map_type = TilemapType::Hexagon(coord_sys);
+    for (
+        entity,
+        size,
+        storage,
+        texture,
+        map_type,
+        mut current_tile_size,
+        mut grid_size,
+        mut transform,
+        is_copy,
+    ) in tilemap_query.iter_mut()
+    {
+        if *size != map_size {
+            for tile_entity in storage.iter().flatten() {
+                commands.entity(*tile_entity).despawn();
+            }
+            commands.entity(entity).despawn();
+            if let (false, TilemapTexture::Single(texture_handle)) = (is_copy, texture) {
+                spawn_tilemap(&mut commands, &settings, texture_handle.clone());
+            }
+        } else if *current_tile_size != tile_size {
+            *current_tile_size = tile_size;
+            *grid_size = tile_size.into();
+            *transform = get_tilemap_center_transform(size, &grid_size, map_type, 0.0);
+        }
+    }
+}
 
-    commands.entity(tilemap_entity).insert((
-        TilemapBundle {
-            grid_size,
-            map_type,
-            tile_size,
-            size: map_size,
-            storage: tile_storage,
-            texture: TilemapTexture::Single(texture_handle.clone()),
-            transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0),
-            ..default()
-        },
-        LastUpdate(-1.0),
-    ));
+/// Regenerates the world when the settings change it, or when a saved world
+/// is pending, and only redraws it otherwise.
+fn request_map_update(
+    settings: Res<Settings>,
+    pending_world: Res<PendingWorld>,
+    mut generated_settings: Local<Option<Settings>>,
+    mut generate_events: EventWriter<GenerateMapEvent>,
+    mut draw_events: EventWriter<DrawMapEvent>,
+) {
+    // Streamed chunks replace the map, which waits to be shown again
+    if settings.streaming {
+        return;
+    }
+    let same_world = generated_settings
+        .as_ref()
+        .is_some_and(|generated| generated.same_world(&settings));
+    if same_world && pending_world.0.is_none() {
+        draw_events.send(DrawMapEvent);
+    } else {
+        generate_events.send(GenerateMapEvent);
+    }
+    *generated_settings = Some(settings.clone());
+}
+
+/// Starts a generation, or draws the pending world instead, whatever step the
+/// map is at.
+fn handle_map_events(
+    settings: Res<Settings>,
+    state: Res<State<MapState>>,
+    mut next_state: ResMut<NextState<MapState>>,
+    mut generation: ResMut<GenerationTask>,
+    mut pending_world: ResMut<PendingWorld>,
+    mut generated_world: ResMut<GeneratedWorld>,
+    mut generate_events: EventReader<GenerateMapEvent>,
+    mut draw_events: EventReader<DrawMapEvent>,
+) {
+    let generate = generate_events.read().count() > 0;
+    let draw = draw_events.read().count() > 0;
+    if generate {
+        // Its settings are outdated, `generate_world` starts a new one
+        generation.cancel();
+        if let Some(world) = pending_world.0.take() {
+            // Saved worlds keep their biomes
+            *generated_world = GeneratedWorld(world, settings.clone());
+            next_state.set(MapState::Rendering);
+        } else {
+            next_state.set(MapState::Generating);
+        }
+    } else if draw && *state.get() == MapState::Ready {
+        next_state.set(MapState::Rendering);
+    }
+}
+
+fn generate_world(
+    settings: Res<Settings>,
+    biomes: Res<Biomes>,
+    mut generation: ResMut<GenerationTask>,
+    mut generated_world: ResMut<GeneratedWorld>,
+    mut next_state: ResMut<NextState<MapState>>,
+) {
+    let world = generation.poll(|progress| {
+        let generator = WorldGenerator::new(settings.clone())
+            .with_biomes(biomes.0.values().cloned())
+            .with_progress(progress);
+        AsyncComputeTaskPool::get().spawn(async move { generator.generate_layers() })
+    });
+    if let Some(world) = world {
+        *generated_world = GeneratedWorld(world, settings.clone());
+        next_state.set(MapState::Classifying);
+    }
+}
+
+fn classify_world(
+    settings: Res<Settings>,
+    biomes: Res<Biomes>,
+    mut generation: ResMut<GenerationTask>,
+    mut generated_world: ResMut<GeneratedWorld>,
+    mut next_state: ResMut<NextState<MapState>>,
+) {
+    let world = generation.poll(|progress| {
+        let generator = WorldGenerator::new(settings.clone())
+            .with_biomes(biomes.0.values().cloned())
+            .with_progress(progress);
+        let mut world = generated_world.0.clone();
+        AsyncComputeTaskPool::get().spawn(async move {
+            generator.classify(&mut world)?;
+            Ok(world)
+        })
+    });
+    if let Some(world) = world {
+        *generated_world = GeneratedWorld(world, settings.clone());
+        next_state.set(MapState::Rendering);
+    }
+}
+
+fn announce_map(generated_world: Res<GeneratedWorld>, mut events: EventWriter<MapGenerated>) {
+    // Redrawing an unchanged world is not a new map
+    if generated_world.is_changed() {
+        events.send(MapGenerated);
+    }
+}
+
+/// Copies the world to the layer grids when it changed since the last
+/// rendering.
+fn fill_layer_grids(
+    generated_world: Res<GeneratedWorld>,
+    mut elevation: ResMut<LayerGrid<TileElevation>>,
+    mut temperature: ResMut<LayerGrid<TileTemperature>>,
+    mut biome: ResMut<LayerGrid<TileBiome>>,
+) {
+    if !generated_world.is_changed() {
+        return;
+    }
+    let world = &generated_world.0;
+    let (width, height) = (world.width, world.height);
+    *elevation = LayerGrid::new(
+        width,
+        height,
+        world.elevation.iter().copied().map(TileElevation).collect(),
+    );
+    *temperature = LayerGrid::new(
+        width,
+        height,
+        world
+            .temperature
+            .iter()
+            .copied()
+            .map(TileTemperature)
+            .collect(),
+    );
+    *biome = LayerGrid::new(
+        width,
+        height,
+        world.biome.iter().copied().map(TileBiome).collect(),
+    );
+}
+
+/// Dirty regions of the layers drawn with the settings, without duplicates.
+fn dirty_regions(
+    settings: &Settings,
+    elevation: &LayerGrid<TileElevation>,
+    temperature: &LayerGrid<TileTemperature>,
+) -> Vec<URect> {
+    let mut regions: Vec<URect> = Vec::new();
+    if settings.elevation {
+        regions.extend(elevation.dirty_regions());
+    }
+    if settings.temperature {
+        regions.extend(temperature.dirty_regions());
+    }
+    regions.sort_by_key(|region| (region.min.y, region.min.x));
+    regions.dedup();
+    regions
 }
 
+/// Recolours the tiles of the dirty regions of the drawn layers, or every tile
+/// when the settings changed or the tilemap is shown again. The tilemap is
+/// hidden with the image renderer and when zoomed out to a summary level.
 fn update_tiles_color(
-    time: Res<Time>,
     settings: Res<Settings>,
-    mut tilemap_query: Query<(&mut Visibility, &mut LastUpdate)>,
-    mut tile_query: Query<(&TileElevation, &TileTemperature, &mut TileColor)>,
+    lod: Res<MapLod>,
+    elevation: Res<LayerGrid<TileElevation>>,
+    temperature: Res<LayerGrid<TileTemperature>>,
+    mut visibility_query: Query<&mut Visibility, With<MapTilemap>>,
+    storage_query: Query<&TileStorage>,
+    mut tile_query: Query<(&TilePos, &mut TileColor)>,
 ) {
-    let mut elevation_renderer = ElevationMapRenderer;
-    let mut temperature_renderer = TemperatureMapRenderer;
-    let current_time = time.elapsed_seconds_f64();
-    for (mut visibility, mut last_update) in tilemap_query.iter_mut() {
-        if current_time - last_update.0 > 0.1 {
-            if !settings.elevation && !settings.temperature {
-                *visibility = Visibility::Hidden;
+    let visible = (settings.elevation || settings.temperature)
+        && settings.render_mode == RenderMode::Tilemap
+        && !settings.streaming
+        && lod.0 == 1;
+    let shown = settings.is_changed() || lod.is_changed();
+    if shown {
+        for mut visibility in visibility_query.iter_mut() {
+            *visibility = if visible {
+                Visibility::Visible
             } else {
-                *visibility = Visibility::Visible;
-
-                for (tile_elevation, tile_temperature, mut tile_color) in tile_query.iter_mut() {
-                    let mut color = Color::srgba(1., 1., 1., 1.);
+                Visibility::Hidden
+            };
+        }
+    }
 
-                    if settings.elevation {
-                        color.mix_assign(elevation_renderer.get_color(tile_elevation), 1.);
-                    }
-                    if settings.temperature {
-                        color.mix_assign(
-                            temperature_renderer.get_color(tile_temperature, &settings),
-                            settings.temperature_factor,
-                        );
+    let regions = dirty_regions(&settings, &elevation, &temperature);
+    let color = |tile_pos: &TilePos| {
+        let tile_elevation = elevation.get(tile_pos)?;
+        let tile_temperature = temperature.get(tile_pos)?;
+        Some(TileColor(renderer::tile_color(
+            &settings,
+            tile_elevation,
+            tile_temperature,
+        )))
+    };
+    // Hidden tiles are redrawn in full once shown
+    if visible && shown {
+        tile_query
+            .par_iter_mut()
+            .for_each(|(tile_pos, mut tile_color)| {
+                if let Some(color) = color(tile_pos) {
+                    *tile_color = color;
+                }
+            });
+    } else if visible {
+        for tile_storage in storage_query.iter() {
+            for region in &regions {
+                for y in region.min.y..region.max.y {
+                    for x in region.min.x..region.max.x {
+                        let tile_pos = TilePos { x, y };
+                        let Some(tile_entity) = tile_storage.checked_get(&tile_pos) else {
+                            continue;
+                        };
+                        if let (Ok((_, mut tile_color)), Some(color)) =
+                            (tile_query.get_mut(tile_entity), color(&tile_pos))
+                        {
+                            *tile_color = color;
+                        }
                     }
-
-                    *tile_color = TileColor(color);
                 }
             }
-            last_update.0 = current_time;
         }
     }
 }
+
+/// Marks the drawn layers clean, and the map ready once rendered.
+fn finish_rendering(
+    state: Res<State<MapState>>,
+    mut elevation: ResMut<LayerGrid<TileElevation>>,
+    mut temperature: ResMut<LayerGrid<TileTemperature>>,
+    mut biome: ResMut<LayerGrid<TileBiome>>,
+    mut next_state: ResMut<NextState<MapState>>,
+) {
+    // Not a change of the layers themselves
+    if elevation.is_dirty() {
+        elevation.bypass_change_detection().clear_dirty();
+    }
+    if temperature.is_dirty() {
+        temperature.bypass_change_detection().clear_dirty();
+    }
+    if biome.is_dirty() {
+        biome.bypass_change_detection().clear_dirty();
+    }
+    if *state.get() == MapState::Rendering {
+        next_state.set(MapState::Ready);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, state::app::StatesPlugin};
+    use bevy_ecs_tilemap::prelude::*;
+    use rstest::rstest;
+
+    use crate::worlds::{
+        map::{biomes::Biomes, layers::REGION_SIZE},
+        settings::Settings,
+    };
+
+    use super::{
+        pipeline, resize_map, spawn_tilemap, world, GeneratedWorld, GenerationTask, LayerGrid,
+        MapGenerated, MapState, PendingWorld, TileElevation, WorldGenerator,
+    };
+
+    fn tilemap_world() -> World {
+        let mut world = World::new();
+        world.insert_resource(Settings {
+            width: 4,
+            height: 3,
+            ..Settings::from_seed(0)
+        });
+        world.run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+            spawn_tilemap(&mut commands, &settings, Handle::default());
+        });
+        world
+    }
+
+    #[rstest]
+    fn test_resize_rebuilds_tilemap() {
+        let mut world = tilemap_world();
+
+        world.resource_mut::<Settings>().width = 6;
+        world.run_system_once(resize_map);
+
+        let sizes: Vec<_> = world
+            .query::<&TilemapSize>()
+            .iter(&world)
+            .copied()
+            .collect();
+        assert_eq!(sizes, [TilemapSize { x: 6, y: 3 }]);
+        assert_eq!(world.query::<&TilePos>().iter(&world).count(), 18);
+    }
+
+    #[rstest]
+    fn test_tile_size_rescales_in_place() {
+        let mut world = tilemap_world();
+        let tilemap = world.query::<(Entity, &TilemapSize)>().single(&world).0;
+
+        world.resource_mut::<Settings>().tile_size = Vec2::new(10., 12.);
+        world.run_system_once(resize_map);
+
+        let (entity, tile_size) = world.query::<(Entity, &TilemapTileSize)>().single(&world);
+        assert_eq!(entity, tilemap);
+        assert_eq!(*tile_size, TilemapTileSize { x: 10., y: 12. });
+        assert_eq!(world.query::<&TilePos>().iter(&world).count(), 12);
+    }
+
+    #[rstest]
+    fn test_streamed_map_has_no_tiles() {
+        let mut world = World::new();
+        world.insert_resource(Settings {
+            width: 4,
+            height: 3,
+            streaming: true,
+            ..Settings::from_seed(0)
+        });
+        world.run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+            spawn_tilemap(&mut commands, &settings, Handle::default());
+        });
+
+        assert_eq!(world.query::<&TilemapSize>().iter(&world).count(), 1);
+        assert_eq!(world.query::<&TilePos>().iter(&world).count(), 0);
+    }
+
+    #[rstest]
+    fn test_streamed_map_allocates_no_tiles() {
+        // Far more tiles than fit in memory, one `Option<Entity>` each
+        let mut world = World::new();
+        world.insert_resource(Settings {
+            width: 1 << 20,
+            height: 1 << 20,
+            streaming: true,
+            ..Settings::from_seed(0)
+        });
+        world.run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+            spawn_tilemap(&mut commands, &settings, Handle::default());
+        });
+
+        world.resource_mut::<Settings>().width += 1;
+        world.run_system_once(resize_map);
+
+        let (size, storage) = world.query::<(&TilemapSize, &TileStorage)>().single(&world);
+        assert_eq!(
+            *size,
+            TilemapSize {
+                x: (1 << 20) + 1,
+                y: 1 << 20
+            }
+        );
+        assert_eq!(storage.size, TilemapSize { x: 0, y: 0 });
+        assert_eq!(storage.iter().count(), 0);
+    }
+
+    /// States of the frames until the map is ready, the ones lasting several
+    /// frames while a task runs only once.
+    fn states_until_ready(app: &mut App) -> Vec<MapState> {
+        let mut states: Vec<MapState> = Vec::new();
+        for _ in 0..1000 {
+            app.update();
+            let state = *app.world().resource::<State<MapState>>().get();
+            if states.last() != Some(&state) {
+                states.push(state);
+            }
+            if state == MapState::Ready && states.len() > 1 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        states
+    }
+
+    #[derive(Resource, Default)]
+    struct Announced(usize);
+
+    #[rstest]
+    fn test_pipeline_generates_then_redraws() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin, pipeline))
+            .init_resource::<Biomes>()
+            .init_resource::<Announced>()
+            .add_systems(
+                Last,
+                |mut events: EventReader<MapGenerated>, mut announced: ResMut<Announced>| {
+                    announced.0 += events.read().count();
+                },
+            )
+            .insert_resource(Settings {
+                width: 4,
+                height: 3,
+                ..Settings::from_seed(0)
+            });
+        app.world_mut()
+            .run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+                spawn_tilemap(&mut commands, &settings, Handle::default());
+            });
+
+        assert_eq!(
+            states_until_ready(&mut app),
+            [
+                MapState::Idle,
+                MapState::Generating,
+                MapState::Classifying,
+                MapState::Rendering,
+                MapState::Ready
+            ]
+        );
+        assert_eq!(app.world().resource::<Announced>().0, 1);
+        let elevation = app.world().resource::<LayerGrid<TileElevation>>();
+        assert_eq!(
+            elevation.get(&TilePos { x: 3, y: 2 }).map(|tile| tile.0),
+            app.world()
+                .resource::<GeneratedWorld>()
+                .0
+                .elevation
+                .last()
+                .copied()
+        );
+
+        app.world_mut()
+            .resource_mut::<Settings>()
+            .temperature_factor = 0.9;
+        assert_eq!(
+            states_until_ready(&mut app),
+            [MapState::Ready, MapState::Rendering, MapState::Ready]
+        );
+        assert_eq!(app.world().resource::<Announced>().0, 1);
+
+        app.world_mut().resource_mut::<Settings>().world_seed = 1;
+        assert_eq!(
+            states_until_ready(&mut app)[..2],
+            [MapState::Ready, MapState::Generating]
+        );
+    }
+
+    #[rstest]
+    fn test_edits_recolour_their_region() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin, pipeline))
+            .init_resource::<Biomes>()
+            .insert_resource(Settings {
+                width: REGION_SIZE + 4,
+                height: 2,
+                ..Settings::from_seed(0)
+            });
+        app.world_mut()
+            .run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+                spawn_tilemap(&mut commands, &settings, Handle::default());
+            });
+        states_until_ready(&mut app);
+
+        let blank = TileColor(Color::srgb(0., 0., 0.));
+        let world = app.world_mut();
+        for mut tile_color in world.query::<&mut TileColor>().iter_mut(world) {
+            *tile_color = blank;
+        }
+        world.resource_mut::<LayerGrid<TileElevation>>().set(
+            &TilePos {
+                x: REGION_SIZE,
+                y: 1,
+            },
+            TileElevation(20.),
+        );
+        app.update();
+
+        let world = app.world_mut();
+        for (tile_pos, tile_color) in world.query::<(&TilePos, &TileColor)>().iter(world) {
+            assert_eq!(
+                *tile_color == blank,
+                tile_pos.x < REGION_SIZE,
+                "{:?}",
+                tile_pos
+            );
+        }
+        assert!(!world.resource::<LayerGrid<TileElevation>>().is_dirty());
+    }
+
+    #[rstest]
+    fn test_settings_change_cancels_generation() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin, pipeline))
+            .init_resource::<Biomes>()
+            .insert_resource(Settings {
+                width: 4,
+                height: 3,
+                ..Settings::from_seed(0)
+            });
+        app.update();
+        app.update();
+        assert_eq!(
+            *app.world().resource::<State<MapState>>().get(),
+            MapState::Generating
+        );
+        let progress = app.world().resource::<GenerationTask>().progress.clone();
+
+        app.world_mut().resource_mut::<Settings>().world_seed = 1;
+        states_until_ready(&mut app);
+
+        assert!(progress.is_cancelled());
+        let settings = app.world().resource::<Settings>().clone();
+        let generated = app.world().resource::<GeneratedWorld>();
+        assert_eq!(generated.1, settings);
+        assert_eq!(
+            generated.0,
+            WorldGenerator::new(settings).generate().unwrap()
+        );
+    }
+
+    #[rstest]
+    fn test_load_during_generation() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin, pipeline))
+            .init_resource::<Biomes>()
+            .insert_resource(Settings {
+                width: 4,
+                height: 3,
+                ..Settings::from_seed(0)
+            });
+        app.update();
+        app.update();
+        assert_eq!(
+            *app.world().resource::<State<MapState>>().get(),
+            MapState::Generating
+        );
+        let progress = app.world().resource::<GenerationTask>().progress.clone();
+
+        let loaded = world::World {
+            width: 4,
+            height: 3,
+            elevation: vec![7.; 12],
+            temperature: vec![3.; 12],
+            biome: vec![None; 12],
+            biome_names: Vec::new(),
+        };
+        app.world_mut().resource_mut::<Settings>().world_seed = 1;
+        app.world_mut().resource_mut::<PendingWorld>().0 = Some(loaded.clone());
+        states_until_ready(&mut app);
+
+        assert!(progress.is_cancelled());
+        assert!(app.world().resource::<PendingWorld>().0.is_none());
+        assert_eq!(app.world().resource::<GeneratedWorld>().0, loaded);
+        assert_eq!(
+            app.world().resource::<LayerGrid<TileElevation>>().values()[0],
+            TileElevation(7.)
+        );
+    }
+}
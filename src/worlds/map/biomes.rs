@@ -10,18 +10,79 @@ use bevy::prelude::*;
 use bevy::reflect::Reflect;
 use serde::Deserialize;
 
-pub(super) fn plugin(app: &mut App) {}
+use crate::worlds::WorldsAssets;
+
+pub const BIOMES_PATH: &str = "assets/biomes";
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<Biomes>()
+        .add_systems(Startup, setup_biomes);
+}
+
+/// Biomes by name.
+#[derive(Resource, Default)]
+pub struct Biomes(pub HashMap<String, Biome>);
+
+fn setup_biomes(mut biomes: ResMut<Biomes>, assets: Res<WorldsAssets>) {
+    match load_biomes(&assets.biomes) {
+        Ok(loaded) => biomes.0 = loaded,
+        Err(e) => eprintln!("Error loading biomes: {}", e),
+    }
+}
 
 #[derive(Reflect, Deserialize, Debug, PartialEq, Clone)]
 pub struct Biome {
     pub name: String,
     pub enabled: Option<bool>,
-    //conditions: Option<HashMap<String, HashMap<String, usize>>>,
+    /// Ranges of layer values (elevation, temperature, latitude, ...) of the biome.
+    pub conditions: Option<HashMap<String, Condition>>,
     //fauna: Option<HashMap<String, Vec<String>>>,
     //flora: Option<HashMap<String, Vec<String>>>,
     pub tiles: Option<HashMap<String, [u8; 3]>>,
 }
 
+impl Biome {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    /// Whether every condition holds, a layer without value fails its condition.
+    /// A biome without conditions never matches.
+    pub fn matches(&self, value: impl Fn(&str) -> Option<f64>) -> bool {
+        match &self.conditions {
+            Some(conditions) => conditions.iter().all(|(layer, condition)| {
+                value(layer).is_some_and(|value| condition.contains(value))
+            }),
+            None => false,
+        }
+    }
+}
+
+/// Inclusive range of a layer value, written `[min, max]`, `{ min = .., max = .. }`
+/// or as a single value.
+#[derive(Reflect, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(untagged)]
+pub enum Condition {
+    Range([f64; 2]),
+    MinMax { min: f64, max: f64 },
+    Value(f64),
+}
+
+impl Condition {
+    pub fn bounds(&self) -> [f64; 2] {
+        match *self {
+            Condition::Range(range) => range,
+            Condition::MinMax { min, max } => [min, max],
+            Condition::Value(value) => [value, value],
+        }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        let [min, max] = self.bounds();
+        min <= value && value <= max
+    }
+}
+
 #[derive(Debug)]
 pub enum LoadBiomeError {
     Io(std::io::Error),
@@ -37,13 +98,17 @@ impl fmt::Display for LoadBiomeError {
     }
 }
 
-pub(crate) fn load_biome(path: &PathBuf) -> Result<Biome, LoadBiomeError> {
+pub fn parse_biome(contents: &str) -> Result<Biome, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+pub fn load_biome(path: &PathBuf) -> Result<Biome, LoadBiomeError> {
     let contents = read_to_string(path).map_err(LoadBiomeError::Io)?;
-    let biome: Biome = toml::from_str(&contents).map_err(LoadBiomeError::Toml)?;
+    let biome: Biome = parse_biome(&contents).map_err(LoadBiomeError::Toml)?;
     Ok(biome)
 }
 
-pub(crate) fn load_biomes(path: &Path) -> Result<HashMap<String, Biome>, io::Error> {
+pub fn load_biomes(path: &Path) -> Result<HashMap<String, Biome>, io::Error> {
     if !path.is_dir() {
         // How do we use log in rust ?
         println!("{:?} is not a directory", path);
@@ -74,9 +139,7 @@ pub(crate) fn load_biomes(path: &Path) -> Result<HashMap<String, Biome>, io::Err
                         };
                     }
                     Err(e) => {
-                        eprintln!("Error loading biome: {:?}", path);
-                        #[cfg(debug_assertions)]
-                        eprintln!("{}", e);
+                        eprintln!("Error loading biome {:?}: {}", path, e);
                     }
                 }
             }
@@ -109,7 +172,7 @@ mod tests {
 
     use crate::worlds::map::biomes::load_biome;
 
-    use super::{load_biomes, Biome, LoadBiomeError};
+    use super::{load_biomes, Biome, Condition, LoadBiomeError};
 
     #[derive(Clone)]
     enum BiomeTestCase {
@@ -117,6 +180,7 @@ mod tests {
         NameOnly,
         WithEmptyTiles,
         WithSomeTiles,
+        WithConditions,
         // Invalid test cases
         MissingName,
         InvalidFormat,
@@ -129,6 +193,7 @@ mod tests {
                 BiomeTestCase::NameOnly => "NameOnly",
                 BiomeTestCase::WithEmptyTiles => "WithEmptyTiles",
                 BiomeTestCase::WithSomeTiles => "WithSomeTiles",
+                BiomeTestCase::WithConditions => "WithConditions",
                 BiomeTestCase::MissingName => "MissingName",
                 BiomeTestCase::InvalidFormat => "InvalidFormat",
                 BiomeTestCase::WithTilesError => "WithTilesError",
@@ -166,6 +231,17 @@ mod tests {
                     water = [2, 2, 2]
                     "#
                 }
+                BiomeTestCase::WithConditions => {
+                    r#"
+                    name = "WithConditions"
+                    enabled = true
+
+                    [conditions]
+                    elevation = [0, 20]
+                    temperature = { min = -5.5, max = 10 }
+                    latitude = 45
+                    "#
+                }
                 // Invalid
                 BiomeTestCase::InvalidFormat => r#"{"foo", "bar"}"#,
                 BiomeTestCase::MissingName => {
@@ -190,11 +266,13 @@ mod tests {
                 BiomeTestCase::NameOnly => Some(Biome {
                     name: "NameOnly".to_string(),
                     enabled: Some(true),
+                    conditions: None,
                     tiles: None,
                 }),
                 BiomeTestCase::WithSomeTiles => Some(Biome {
                     name: "WithSomeTiles".to_string(),
                     enabled: Some(true),
+                    conditions: None,
                     tiles: Some(
                         [
                             ("grass".to_string(), [1, 1, 1]),
@@ -204,9 +282,30 @@ mod tests {
                         .collect(),
                     ),
                 }),
+                BiomeTestCase::WithConditions => Some(Biome {
+                    name: "WithConditions".to_string(),
+                    enabled: Some(true),
+                    conditions: Some(
+                        [
+                            ("elevation".to_string(), Condition::Range([0., 20.])),
+                            (
+                                "temperature".to_string(),
+                                Condition::MinMax {
+                                    min: -5.5,
+                                    max: 10.,
+                                },
+                            ),
+                            ("latitude".to_string(), Condition::Value(45.)),
+                        ]
+                        .into_iter()
+                        .collect(),
+                    ),
+                    tiles: None,
+                }),
                 BiomeTestCase::WithEmptyTiles => Some(Biome {
                     name: "WithEmptyTiles".to_string(),
                     enabled: Some(true),
+                    conditions: None,
                     tiles: Some([].into_iter().collect()),
                 }),
                 // Invalid
@@ -251,6 +350,7 @@ mod tests {
     #[case::name_only(BiomeTestCase::NameOnly)]
     #[case::with_empty_tiles(BiomeTestCase::WithEmptyTiles)]
     #[case::with_some_tiles(BiomeTestCase::WithSomeTiles)]
+    #[case::with_conditions(BiomeTestCase::WithConditions)]
     fn test_load_biome_success(#[case] biome_test_case: BiomeTestCase) -> Result<(), Error> {
         let filepath = materialize_biome_test_case(&biome_test_case, None)?;
         let biome = load_biome(&filepath).expect("Valid biome file should load successfully");
@@ -275,6 +375,34 @@ mod tests {
         Ok(())
     }
 
+    #[rstest]
+    #[case::inside(10., 5., 45., true)]
+    #[case::on_bounds(0., -5.5, 45., true)]
+    #[case::outside(10., 20., 45., false)]
+    fn test_biome_matches(
+        #[case] elevation: f64,
+        #[case] temperature: f64,
+        #[case] latitude: f64,
+        #[case] expected: bool,
+    ) {
+        let biome = BiomeTestCase::WithConditions.expectation().unwrap();
+        let value = |layer: &str| match layer {
+            "elevation" => Some(elevation),
+            "temperature" => Some(temperature),
+            "latitude" => Some(latitude),
+            _ => None,
+        };
+
+        assert_eq!(biome.matches(value), expected);
+    }
+
+    #[rstest]
+    fn test_biome_matches_unknown_layer() {
+        let biome = BiomeTestCase::WithConditions.expectation().unwrap();
+
+        assert!(!biome.matches(|_| None));
+    }
+
     #[rstest]
     fn test_load_biomes_success() -> Result<(), Error> {
         let mut biome_test_cases = [BiomeTestCase::NameOnly, BiomeTestCase::WithSomeTiles].to_vec();
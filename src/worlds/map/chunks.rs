@@ -0,0 +1,631 @@
+//! Streamed open world: with `settings.streaming`, the map is replaced by
+//! chunks of [`CHUNK_SIZE`] tiles generated around the camera, unloaded once
+//! far from it, and cached to disk so that coming back does not generate them
+//! again. The cache keeps the [`MAX_CACHED_CHUNKS`] last used chunks.
+
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, read_dir, remove_file, File},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::worlds::{
+    save::{load_world, save_world},
+    settings::{Settings, DEFAULT_SETTINGS_PATH},
+};
+
+use super::{
+    biomes::Biomes,
+    generator::{
+        elevation::TileElevation,
+        temperature::TileTemperature,
+        world::{World, WorldGenerator, CHUNK_SIZE},
+    },
+    renderer, MapTilemap,
+};
+
+/// Directory the chunks are cached to, one directory per seed, next to the
+/// settings file.
+pub const CHUNK_CACHE_PATH: &str = "cache/chunks";
+/// Chunks kept in the cache, the least recently used are removed beyond.
+pub const MAX_CACHED_CHUNKS: usize = 1024;
+
+/// Chunks loaded around those the camera sees.
+const LOAD_MARGIN: i32 = 1;
+/// Most chunks loaded from the camera in each direction, however far it zooms
+/// out, besides the margins.
+const MAX_LOAD_RADIUS: i32 = 4;
+/// Chunks further than the loaded ones by more than this are unloaded, so that
+/// panning back and forth does not reload them.
+const UNLOAD_MARGIN: i32 = 1;
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<StreamedChunks>()
+        .init_resource::<ChunkCache>()
+        .add_systems(
+            Update,
+            (
+                reset_chunks.run_if(resource_changed::<Settings>),
+                (request_chunks, spawn_chunks, unload_chunks, evict_cache)
+                    .chain()
+                    .run_if(|settings: Res<Settings>| settings.streaming),
+            )
+                .chain(),
+        );
+}
+
+/// Directory chunks are cached to, `None` to always generate them.
+#[derive(Resource, Debug, Clone)]
+pub struct ChunkCache(pub Option<PathBuf>);
+
+impl ChunkCache {
+    /// The [`CHUNK_CACHE_PATH`] next to a settings file.
+    pub fn next_to(settings_path: &Path) -> Self {
+        let dir = settings_path.parent().unwrap_or(Path::new(""));
+        Self(Some(dir.join(CHUNK_CACHE_PATH)))
+    }
+}
+
+impl Default for ChunkCache {
+    fn default() -> Self {
+        Self::next_to(Path::new(DEFAULT_SETTINGS_PATH))
+    }
+}
+
+/// Loaded chunks, and the ones being loaded in the background.
+#[derive(Resource, Default)]
+pub struct StreamedChunks {
+    loaded: HashMap<IVec2, Entity>,
+    pending: HashMap<IVec2, Task<World>>,
+    /// Whether chunks were loaded since the cache was last trimmed.
+    used_cache: bool,
+    /// Trimming of the cache in the background, one at a time.
+    eviction: Option<Task<()>>,
+}
+
+impl StreamedChunks {
+    pub fn loaded(&self) -> impl Iterator<Item = &IVec2> {
+        self.loaded.keys()
+    }
+}
+
+/// Tilemap of a chunk, its first tile at `coords * CHUNK_SIZE`.
+#[derive(Component)]
+pub struct Chunk {
+    pub coords: IVec2,
+    pub world: World,
+}
+
+/// Grid of the chunks, from the settings rather than the tilemap which may be
+/// rescaled later in the frame.
+fn chunk_grid_size(settings: &Settings) -> TilemapGridSize {
+    TilemapGridSize {
+        x: settings.tile_size.x,
+        y: settings.tile_size.y,
+    }
+}
+
+/// Size of a chunk in world units.
+fn chunk_extent(grid_size: &TilemapGridSize) -> Vec2 {
+    Vec2::new(
+        CHUNK_SIZE as f32 * grid_size.x,
+        CHUNK_SIZE as f32 * 0.75 * grid_size.y,
+    )
+}
+
+/// First and last chunks to keep loaded for a camera: those it sees, and
+/// `margin` more on each side.
+fn chunks_around(
+    camera: &GlobalTransform,
+    projection: &OrthographicProjection,
+    extent: Vec2,
+    margin: i32,
+) -> (IVec2, IVec2) {
+    let position = camera.translation().truncate();
+    let half_size = projection.area.half_size();
+    let centre = (position / extent).floor().as_ivec2();
+    let min = ((position - half_size) / extent).floor().as_ivec2() - margin;
+    let max = ((position + half_size) / extent).floor().as_ivec2() + margin;
+    (
+        min.max(centre - MAX_LOAD_RADIUS - margin),
+        max.min(centre + MAX_LOAD_RADIUS + margin),
+    )
+}
+
+/// The size of the map is part of the name: the latitudes of a chunk depend on
+/// it, while the settings it is saved with are sized like the chunk.
+fn chunk_path(dir: &Path, settings: &Settings, coords: IVec2) -> PathBuf {
+    dir.join(settings.world_seed.to_string()).join(format!(
+        "{}x{}_{}_{}.sav",
+        settings.width, settings.height, coords.x, coords.y
+    ))
+}
+
+/// Settings a chunk is saved with, sized like the chunk as saves must be.
+fn chunk_settings(settings: &Settings) -> Settings {
+    Settings {
+        width: CHUNK_SIZE,
+        height: CHUNK_SIZE,
+        ..settings.clone()
+    }
+}
+
+/// Loads a chunk from the cache in `dir` when it was generated with the same
+/// world settings, marking it as used, or generates it and caches it.
+fn load_or_generate(generator: &WorldGenerator, coords: IVec2, dir: Option<&Path>) -> World {
+    let settings = chunk_settings(generator.settings());
+    let path = dir.map(|dir| chunk_path(dir, generator.settings(), coords));
+    if let Some(path) = &path {
+        if let Ok((cached_settings, world)) = load_world(path) {
+            if cached_settings.same_world(&settings) {
+                let touched = File::options()
+                    .write(true)
+                    .open(path)
+                    .and_then(|file| file.set_modified(SystemTime::now()));
+                if let Err(e) = touched {
+                    eprintln!("Error touching cached chunk {:?}: {}", path, e);
+                }
+                return world;
+            }
+        }
+    }
+
+    let world = generator.generate_chunk(coords);
+    if let Some(path) = path {
+        let saved = path
+            .parent()
+            .map_or(Ok(()), create_dir_all)
+            .map_err(|e| e.to_string())
+            .and_then(|_| save_world(&world, &settings, &path).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            eprintln!("Error caching chunk {:?}: {}", path, e);
+        }
+    }
+    world
+}
+
+/// Removes the least recently used chunks of the cache, of every seed, beyond
+/// `max`. Chunks removed meanwhile are skipped.
+fn evict_chunks(dir: &Path, max: usize) -> io::Result<()> {
+    let ignore_missing = |result: io::Result<()>| match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    };
+    let mut chunks: Vec<(SystemTime, PathBuf)> = Vec::new();
+    for seed_dir in read_dir(dir)? {
+        let seed_dir = seed_dir?.path();
+        if !seed_dir.is_dir() {
+            continue;
+        }
+        for chunk in read_dir(&seed_dir)? {
+            let chunk = chunk?;
+            ignore_missing(chunk.metadata().and_then(|metadata| {
+                chunks.push((metadata.modified()?, chunk.path()));
+                Ok(())
+            }))?;
+        }
+    }
+    if chunks.len() <= max {
+        return Ok(());
+    }
+
+    chunks.sort();
+    for (_, path) in &chunks[..chunks.len() - max] {
+        ignore_missing(remove_file(path))?;
+    }
+    Ok(())
+}
+
+/// Trims the cache in the background once chunks were loaded, never twice at
+/// the same time.
+fn evict_cache(cache: Res<ChunkCache>, mut chunks: ResMut<StreamedChunks>) {
+    if let Some(task) = &mut chunks.eviction {
+        if block_on(future::poll_once(task)).is_none() {
+            return;
+        }
+        chunks.eviction = None;
+    }
+    let Some(dir) = cache.0.clone() else {
+        return;
+    };
+    if !chunks.used_cache {
+        return;
+    }
+    chunks.used_cache = false;
+    chunks.eviction = Some(AsyncComputeTaskPool::get().spawn(async move {
+        if let Err(e) = evict_chunks(&dir, MAX_CACHED_CHUNKS) {
+            eprintln!("Error evicting cached chunks from {:?}: {}", dir, e);
+        }
+    }));
+}
+
+/// Unloads every chunk when the world or the tile size changes, or streaming
+/// stops, and recolours them when only their drawing changes.
+fn reset_chunks(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut chunks: ResMut<StreamedChunks>,
+    mut streamed_settings: Local<Option<Settings>>,
+    chunk_query: Query<(&Chunk, &TileStorage)>,
+    mut tile_query: Query<&mut TileColor>,
+) {
+    // Chunks are laid out with the tile size they were spawned with
+    let same_world = streamed_settings.as_ref().is_some_and(|streamed| {
+        streamed.same_world(&settings) && streamed.tile_size == settings.tile_size
+    });
+    *streamed_settings = Some(settings.clone());
+
+    if same_world && settings.streaming {
+        for (chunk, tile_storage) in chunk_query.iter() {
+            color_chunk(&settings, chunk, tile_storage, &mut tile_query);
+        }
+        return;
+    }
+    for (_, entity) in chunks.loaded.drain() {
+        despawn_chunk(&mut commands, entity, &chunk_query);
+    }
+    chunks.pending.clear();
+}
+
+/// Starts loading the missing chunks around every camera.
+fn request_chunks(
+    settings: Res<Settings>,
+    biomes: Res<Biomes>,
+    cache: Res<ChunkCache>,
+    mut chunks: ResMut<StreamedChunks>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+    tilemap_query: Query<(), With<MapTilemap>>,
+) {
+    // The tilemap is needed to draw the chunks
+    if tilemap_query.is_empty() {
+        return;
+    }
+    let extent = chunk_extent(&chunk_grid_size(&settings));
+
+    for (camera, projection) in camera_query.iter() {
+        let (min, max) = chunks_around(camera, projection, extent, LOAD_MARGIN);
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let coords = IVec2::new(x, y);
+                if chunks.loaded.contains_key(&coords) || chunks.pending.contains_key(&coords) {
+                    continue;
+                }
+                let generator =
+                    WorldGenerator::new(settings.clone()).with_biomes(biomes.0.values().cloned());
+                let dir = cache.0.clone();
+                let task = AsyncComputeTaskPool::get()
+                    .spawn(async move { load_or_generate(&generator, coords, dir.as_deref()) });
+                chunks.pending.insert(coords, task);
+            }
+        }
+    }
+}
+
+/// Spawns the tilemaps of the chunks done loading.
+fn spawn_chunks(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut chunks: ResMut<StreamedChunks>,
+    tilemap_query: Query<&TilemapTexture, With<MapTilemap>>,
+) {
+    let Ok(texture) = tilemap_query.get_single() else {
+        return;
+    };
+    let grid_size = chunk_grid_size(&settings);
+    let extent = chunk_extent(&grid_size);
+
+    let mut done = Vec::new();
+    for (coords, task) in chunks.pending.iter_mut() {
+        if let Some(world) = block_on(future::poll_once(task)) {
+            done.push((*coords, world));
+        }
+    }
+
+    chunks.used_cache |= !done.is_empty();
+    for (coords, world) in done {
+        chunks.pending.remove(&coords);
+        let size = TilemapSize {
+            x: CHUNK_SIZE,
+            y: CHUNK_SIZE,
+        };
+        let chunk_entity = commands.spawn_empty().id();
+        let mut tile_storage = TileStorage::empty(size);
+        for index in 0..world.elevation.len() {
+            let tile_pos = world.tile_pos(index);
+            let color = renderer::tile_color(
+                &settings,
+                &TileElevation(world.elevation[index]),
+                &TileTemperature(world.temperature[index]),
+            );
+            let tile_entity = commands
+                .spawn(TileBundle {
+                    position: tile_pos,
+                    tilemap_id: TilemapId(chunk_entity),
+                    color: TileColor(color),
+                    ..default()
+                })
+                .id();
+            tile_storage.set(&tile_pos, tile_entity);
+        }
+
+        commands.entity(chunk_entity).insert((
+            TilemapBundle {
+                grid_size,
+                map_type: TilemapType::Hexagon(HexCoordSystem::RowEven),
+                tile_size: TilemapTileSize {
+                    x: grid_size.x,
+                    y: grid_size.y,
+                },
+                size,
+                storage: tile_storage,
+                texture: texture.clone(),
+                transform: Transform::from_translation((coords.as_vec2() * extent).extend(0.)),
+                ..default()
+            },
+            Chunk { coords, world },
+        ));
+        chunks.loaded.insert(coords, chunk_entity);
+    }
+}
+
+/// Unloads the chunks far from every camera, and stops loading them.
+fn unload_chunks(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut chunks: ResMut<StreamedChunks>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+    chunk_query: Query<(&Chunk, &TileStorage)>,
+) {
+    let extent = chunk_extent(&chunk_grid_size(&settings));
+    let kept: Vec<(IVec2, IVec2)> = camera_query
+        .iter()
+        .map(|(camera, projection)| {
+            chunks_around(camera, projection, extent, LOAD_MARGIN + UNLOAD_MARGIN)
+        })
+        .collect();
+    let is_kept = |coords: &IVec2| {
+        kept.iter()
+            .any(|(min, max)| coords.cmpge(*min).all() && coords.cmple(*max).all())
+    };
+
+    chunks.pending.retain(|coords, _| is_kept(coords));
+    let far: Vec<IVec2> = chunks
+        .loaded
+        .keys()
+        .filter(|coords| !is_kept(coords))
+        .copied()
+        .collect();
+    for coords in far {
+        if let Some(entity) = chunks.loaded.remove(&coords) {
+            despawn_chunk(&mut commands, entity, &chunk_query);
+        }
+    }
+}
+
+fn despawn_chunk(
+    commands: &mut Commands,
+    entity: Entity,
+    chunk_query: &Query<(&Chunk, &TileStorage)>,
+) {
+    if let Ok((_, tile_storage)) = chunk_query.get(entity) {
+        for tile_entity in tile_storage.iter().flatten() {
+            commands.entity(*tile_entity).despawn();
+        }
+    }
+    commands.entity(entity).despawn();
+}
+
+fn color_chunk(
+    settings: &Settings,
+    chunk: &Chunk,
+    tile_storage: &TileStorage,
+    tile_query: &mut Query<&mut TileColor>,
+) {
+    for index in 0..chunk.world.elevation.len() {
+        let tile_pos = chunk.world.tile_pos(index);
+        let Some(tile_entity) = tile_storage.checked_get(&tile_pos) else {
+            continue;
+        };
+        if let Ok(mut tile_color) = tile_query.get_mut(tile_entity) {
+            *tile_color = TileColor(renderer::tile_color(
+                settings,
+                &TileElevation(chunk.world.elevation[index]),
+                &TileTemperature(chunk.world.temperature[index]),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        time::{Duration, Instant},
+    };
+
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+    use bevy_ecs_tilemap::prelude::*;
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use crate::worlds::{
+        map::{biomes::Biomes, generator::world::WorldGenerator, spawn_tilemap},
+        save::save_world,
+        settings::Settings,
+    };
+
+    use super::{
+        chunk_extent, chunk_grid_size, chunk_settings, evict_chunks, load_or_generate, plugin,
+        Chunk, ChunkCache, StreamedChunks, MAX_LOAD_RADIUS,
+    };
+
+    fn loaded_chunks(app: &App) -> Vec<IVec2> {
+        let mut loaded: Vec<IVec2> = app
+            .world()
+            .resource::<StreamedChunks>()
+            .loaded()
+            .copied()
+            .collect();
+        loaded.sort_by_key(|coords| (coords.y, coords.x));
+        loaded
+    }
+
+    fn update_until_loaded(app: &mut App) {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(30) {
+            app.update();
+            if app.world().resource::<StreamedChunks>().pending.is_empty() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[rstest]
+    fn test_chunks_follow_the_camera() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, plugin))
+            .init_resource::<Biomes>()
+            .insert_resource(ChunkCache(None))
+            .insert_resource(Settings {
+                width: 4,
+                height: 3,
+                streaming: true,
+                ..Settings::from_seed(0)
+            });
+        app.world_mut()
+            .run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+                spawn_tilemap(&mut commands, &settings, Handle::default());
+            });
+        let settings = app.world().resource::<Settings>().clone();
+        let extent = chunk_extent(&chunk_grid_size(&settings));
+        let centre_of = |coords: IVec2| (coords.as_vec2() * extent + extent / 2.).extend(0.);
+        // Without transform propagation, the global transform is moved
+        let camera = app
+            .world_mut()
+            .spawn(Camera2dBundle::default())
+            .insert(GlobalTransform::from_translation(centre_of(IVec2::ZERO)))
+            .id();
+
+        // The default projection sees less than a chunk
+        update_until_loaded(&mut app);
+        let around_origin: Vec<IVec2> = (-1..=1)
+            .flat_map(|y| (-1..=1).map(move |x| IVec2::new(x, y)))
+            .collect();
+        assert_eq!(loaded_chunks(&app), around_origin);
+
+        let far = IVec2::splat(MAX_LOAD_RADIUS * 3);
+        app.world_mut()
+            .entity_mut(camera)
+            .insert(GlobalTransform::from_translation(centre_of(far)));
+        update_until_loaded(&mut app);
+
+        let around_far: Vec<IVec2> = around_origin.iter().map(|coords| *coords + far).collect();
+        assert_eq!(loaded_chunks(&app), around_far);
+        let world = app.world_mut();
+        assert_eq!(world.query::<&Chunk>().iter(world).count(), 9);
+
+        // Chunks are laid out again with a new tile size
+        app.world_mut().resource_mut::<Settings>().tile_size *= 2.;
+        app.world_mut()
+            .entity_mut(camera)
+            .insert(GlobalTransform::from_translation(
+                centre_of(IVec2::ZERO) * 2.,
+            ));
+        update_until_loaded(&mut app);
+
+        assert_eq!(loaded_chunks(&app), around_origin);
+        let world = app.world_mut();
+        for (chunk, grid_size, transform) in world
+            .query::<(&Chunk, &TilemapGridSize, &Transform)>()
+            .iter(world)
+        {
+            assert_eq!(grid_size.x, settings.tile_size.x * 2.);
+            assert_eq!(
+                transform.translation.truncate(),
+                chunk.coords.as_vec2() * extent * 2.
+            );
+        }
+    }
+
+    #[rstest]
+    fn test_chunks_are_cached() {
+        let cache = tempdir().unwrap();
+        let settings = Settings::from_seed(0);
+        let path = cache.path().join("0").join("500x500_0_0.sav");
+        let generator = WorldGenerator::new(settings.clone());
+
+        let generated = load_or_generate(&generator, IVec2::ZERO, Some(cache.path()));
+        assert!(path.exists());
+
+        // Loaded from the cache rather than generated again
+        let mut cached = generated.clone();
+        cached.elevation[0] += 1.;
+        save_world(&cached, &chunk_settings(&settings), &path).unwrap();
+        let saved_at = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(
+            load_or_generate(&generator, IVec2::ZERO, Some(cache.path())),
+            cached
+        );
+        // And marked as recently used
+        assert!(std::fs::metadata(&path).unwrap().modified().unwrap() > saved_at);
+
+        // Unless the map was resized, which moves its latitudes
+        let resized = WorldGenerator::new(Settings {
+            height: 100,
+            ..settings.clone()
+        });
+        assert_eq!(
+            load_or_generate(&resized, IVec2::ZERO, Some(cache.path())),
+            resized.generate_chunk(IVec2::ZERO)
+        );
+
+        // Unless cached for another world
+        let mut other_settings = settings.clone();
+        other_settings.elevation_gen.octaves += 1;
+        let other = WorldGenerator::new(other_settings);
+        assert_ne!(
+            load_or_generate(&other, IVec2::ZERO, Some(cache.path())),
+            cached
+        );
+    }
+
+    #[rstest]
+    fn test_evict_chunks() {
+        let cache = tempdir().unwrap();
+        for (seed, name) in [("0", "0_0.sav"), ("1", "0_0.sav"), ("0", "1_0.sav")] {
+            let dir = cache.path().join(seed);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(name), []).unwrap();
+            // Apart enough for the modification times
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        evict_chunks(cache.path(), 2).unwrap();
+
+        assert!(!cache.path().join("0").join("0_0.sav").exists());
+        assert!(cache.path().join("1").join("0_0.sav").exists());
+        assert!(cache.path().join("0").join("1_0.sav").exists());
+    }
+
+    #[rstest]
+    #[case::relative("settings.toml", "cache/chunks")]
+    #[case::nested("config/worlds.toml", "config/cache/chunks")]
+    #[case::absolute("/etc/worlds/settings.toml", "/etc/worlds/cache/chunks")]
+    fn test_cache_is_next_to_settings(#[case] settings_path: &str, #[case] expected: &str) {
+        assert_eq!(
+            ChunkCache::next_to(Path::new(settings_path)).0,
+            Some(PathBuf::from(expected))
+        );
+    }
+}
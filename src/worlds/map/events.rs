@@ -1,7 +1,14 @@
 use bevy::prelude::Event;
 
+/// Redraws the map from the tiles, without generating it again.
 #[derive(Event)]
-pub(crate) struct DrawMapEvent;
+pub struct DrawMapEvent;
 
+/// Generates the map from the settings, then draws it.
 #[derive(Event)]
-pub(crate) struct GenerateMapEvent;
+pub struct GenerateMapEvent;
+
+/// Sent once a newly generated or loaded world is drawn, it is then in
+/// `GeneratedWorld`.
+#[derive(Event)]
+pub struct MapGenerated;
@@ -1,12 +1,227 @@
-use bevy::prelude::*;
+use std::f64::consts::TAU;
+
+use bevy::{math::DVec3, prelude::*};
 use bevy_ecs_tilemap::tiles::{TilePos, TileStorage};
 
-use crate::worlds::settings::Settings;
+use noise::{NoiseFn, Perlin};
+
+use crate::worlds::{
+    map::MAX_PERLIN_SCALE,
+    seed::{NoiseSeed, SeedStream},
+    settings::{PerlinConfiguration, Settings},
+};
 
+pub mod biome;
 pub mod elevation;
+pub mod steps;
 pub mod temperature;
+pub mod world;
 
 pub trait MapGenerator: Send + Sync {
     fn get_value(&self, tile_pos: &TilePos, settings: &Settings) -> f64;
     fn get_min_max(settings: &Settings) -> [f64; 2];
 }
+
+/// Sums the octaves of the configuration, `point` gives the sample point of
+/// an octave from its frequency.
+pub fn fractal_noise(
+    perlin: &Perlin,
+    config: &PerlinConfiguration,
+    point: impl Fn(f64) -> [f64; 3],
+) -> f64 {
+    (0..config.octaves).fold(0., |value, o| {
+        let frequency = config.lacunarity.powi(o);
+        let amplitude = config.persistance.powi(o);
+        value + perlin.get(point(frequency)) * amplitude
+    })
+}
+
+/// Perlin noise of a layer for a world seed, built once per generation rather
+/// than for every sample.
+pub struct NoiseLayer {
+    perlin: Perlin,
+    config: PerlinConfiguration,
+    noise_scale: f64,
+    offset: [f64; 2],
+}
+
+impl NoiseLayer {
+    pub fn new(seed: u64, stream: SeedStream, config: &PerlinConfiguration) -> Self {
+        let noise_seed = NoiseSeed::derive(seed, stream);
+        Self {
+            perlin: Perlin::new(noise_seed.seed),
+            config: config.clone(),
+            noise_scale: config.noise_scale.clamp(0., MAX_PERLIN_SCALE),
+            offset: [
+                noise_seed.offset.x + config.offset.x as f64,
+                noise_seed.offset.y + config.offset.y as f64,
+            ],
+        }
+    }
+
+    /// Fractal noise of a tile, see [`noise_point`].
+    pub fn at_tile(&self, tile_pos: &TilePos, settings: &Settings) -> f64 {
+        fractal_noise(&self.perlin, &self.config, |frequency| {
+            noise_point(tile_pos, settings, self.noise_scale, frequency, self.offset)
+        })
+    }
+
+    /// Fractal noise at a position of an unbounded map, in tiles, the same as
+    /// [`NoiseLayer::at_tile`] on maps that do not wrap.
+    pub fn at_position(&self, x: f64, y: f64) -> f64 {
+        fractal_noise(&self.perlin, &self.config, |frequency| {
+            [
+                x / self.noise_scale * frequency + self.offset[0],
+                y / self.noise_scale * frequency + self.offset[1],
+                0.0,
+            ]
+        })
+    }
+
+    /// Fractal noise of a position of the unit sphere, see [`sphere_point`].
+    pub fn at_sphere(&self, position: DVec3, settings: &Settings) -> f64 {
+        fractal_noise(&self.perlin, &self.config, |frequency| {
+            sphere_point(position, settings, self.noise_scale, frequency, self.offset)
+        })
+    }
+}
+
+/// Noise sample point of a tile for an octave.
+///
+/// When the world wraps, x goes around a cylinder whose circumference is the
+/// width of the map, so that the east and west edges match without stretching.
+pub fn noise_point(
+    tile_pos: &TilePos,
+    settings: &Settings,
+    noise_scale: f64,
+    frequency: f64,
+    offset: [f64; 2],
+) -> [f64; 3] {
+    let sample_y = tile_pos.y as f64 / noise_scale * frequency + offset[1];
+    if !settings.wrap {
+        let sample_x = tile_pos.x as f64 / noise_scale * frequency + offset[0];
+        return [sample_x, sample_y, 0.0];
+    }
+
+    let radius = settings.width as f64 / TAU / noise_scale * frequency;
+    let angle = tile_pos.x as f64 / settings.width as f64 * TAU;
+    [
+        radius * angle.cos() + offset[0],
+        sample_y,
+        radius * angle.sin(),
+    ]
+}
+
+/// Noise sample point of a position of the unit sphere for an octave, on a
+/// sphere whose circumference is the width of the map like the cylinder.
+pub fn sphere_point(
+    position: DVec3,
+    settings: &Settings,
+    noise_scale: f64,
+    frequency: f64,
+    offset: [f64; 2],
+) -> [f64; 3] {
+    let radius = settings.width as f64 / TAU / noise_scale * frequency;
+    let point = position * radius + DVec3::new(offset[0], offset[1], 0.);
+    point.to_array()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_tilemap::tiles::TilePos;
+    use rstest::rstest;
+
+    use crate::worlds::settings::Settings;
+
+    use super::elevation::ElevationGenerator;
+    use super::temperature::TemperatureGenerator;
+    use super::MapGenerator;
+
+    fn small_settings(seed: u64) -> Settings {
+        Settings {
+            width: 16,
+            height: 16,
+            ..Settings::from_seed(seed)
+        }
+    }
+
+    fn generate_bits(settings: &Settings) -> Vec<(u64, u64)> {
+        let elevation = ElevationGenerator::new(settings);
+        let temperature = TemperatureGenerator::new(settings);
+        let mut values = Vec::new();
+        for x in 0..settings.width {
+            for y in 0..settings.height {
+                let tile_pos = TilePos { x, y };
+                values.push((
+                    elevation.get_value(&tile_pos, settings).to_bits(),
+                    temperature.get_value(&tile_pos, settings).to_bits(),
+                ));
+            }
+        }
+        values
+    }
+
+    #[rstest]
+    fn test_same_seed_same_world() {
+        assert_eq!(
+            generate_bits(&small_settings(1234)),
+            generate_bits(&small_settings(1234))
+        );
+    }
+
+    #[rstest]
+    fn test_different_seed_different_world() {
+        assert_ne!(
+            generate_bits(&small_settings(1234)),
+            generate_bits(&small_settings(4321))
+        );
+    }
+
+    #[rstest]
+    #[case::edge(TilePos { x: 0, y: 9 }, -4.09778291587215)]
+    #[case::inner(TilePos { x: 3, y: 7 }, -2.816462479639142)]
+    #[case::corner(TilePos { x: 15, y: 15 }, -5.781839547255906)]
+    fn test_elevation_is_stable(#[case] tile_pos: TilePos, #[case] expected: f64) {
+        // Only elevation is pinned: temperature goes through `cos`, whose last
+        // bit is up to the platform libm.
+        let settings = small_settings(1234);
+
+        assert_eq!(
+            ElevationGenerator::new(&settings)
+                .get_value(&tile_pos, &settings)
+                .to_bits(),
+            expected.to_bits()
+        );
+    }
+
+    #[rstest]
+    fn test_wrap_is_seamless() {
+        let settings = Settings {
+            wrap: true,
+            ..small_settings(1234)
+        };
+
+        let elevation = ElevationGenerator::new(&settings);
+        let temperature = TemperatureGenerator::new(&settings);
+        for y in 0..settings.height {
+            // One column past the east edge is the first column again
+            let east = TilePos {
+                x: settings.width,
+                y,
+            };
+            let west = TilePos { x: 0, y };
+            for (a, b) in [
+                (
+                    elevation.get_value(&east, &settings),
+                    elevation.get_value(&west, &settings),
+                ),
+                (
+                    temperature.get_value(&east, &settings),
+                    temperature.get_value(&west, &settings),
+                ),
+            ] {
+                assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use crate::worlds::{map::biomes::Biome, settings::Settings};
+
+use super::{elevation::ElevationGenerator, temperature::TemperatureGenerator, MapGenerator};
+
+/// Index of a biome in [`BiomeClassifier::names`].
+pub type BiomeId = u16;
+
+/// Range of the values a layer takes with these settings, `None` for unknown
+/// layers which biome conditions cannot use.
+pub fn layer_range(layer: &str, settings: &Settings) -> Option<[f64; 2]> {
+    match layer {
+        "elevation" => Some(ElevationGenerator::get_min_max(settings)),
+        "temperature" => Some(TemperatureGenerator::get_min_max(settings)),
+        "latitude" => Some([-90., 90.]),
+        _ => None,
+    }
+}
+
+/// Biome of a tile, the values of `LayerGrid<TileBiome>`.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct TileBiome(pub Option<BiomeId>);
+
+/// Picks the biome of a tile from its layer values.
+///
+/// Only enabled biomes with conditions take part, sorted by name so that ids
+/// and ties between overlapping biomes do not depend on the loading order.
+#[derive(Default, Clone)]
+pub struct BiomeClassifier {
+    biomes: Vec<Biome>,
+}
+
+impl BiomeClassifier {
+    pub fn new(biomes: impl IntoIterator<Item = Biome>) -> Self {
+        let mut biomes: Vec<Biome> = biomes
+            .into_iter()
+            .filter(|biome| biome.is_enabled() && biome.conditions.is_some())
+            .collect();
+        biomes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self { biomes }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.biomes.iter().map(|biome| biome.name.clone()).collect()
+    }
+
+    pub fn classify(&self, elevation: f64, temperature: f64, latitude: f64) -> Option<BiomeId> {
+        let value = |layer: &str| match layer {
+            "elevation" => Some(elevation),
+            "temperature" => Some(temperature),
+            "latitude" => Some(latitude),
+            _ => None,
+        };
+
+        self.biomes
+            .iter()
+            .position(|biome| biome.matches(value))
+            .map(|id| id as BiomeId)
+    }
+}
@@ -0,0 +1,356 @@
+//! Generation steps: the ordered list of what generates a world, declared in
+//! the settings as `[[steps]]` tables so that presets can describe different
+//! kinds of worlds.
+//!
+//! ```toml
+//! [[steps]]
+//! kind = "elevation"
+//!
+//! [[steps]]
+//! kind = "shape"
+//! strength = 0.6
+//! inputs = ["elevation"]
+//! outputs = ["elevation"]
+//! ```
+
+use std::fmt;
+
+use bevy_ecs_tilemap::map::TilemapSize;
+use serde::{Deserialize, Serialize};
+
+use crate::worlds::hex;
+use crate::worlds::map::shapes::{CircleCenteredShape, ShapeGenerator};
+use crate::worlds::settings::Settings;
+
+use super::world::{Cancelled, GenerationProgress, World};
+
+/// Layers read and written by the steps.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StepLayer {
+    Elevation,
+    Temperature,
+    Biome,
+}
+
+impl fmt::Display for StepLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StepLayer::Elevation => "elevation",
+            StepLayer::Temperature => "temperature",
+            StepLayer::Biome => "biome",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Step {
+    /// Shown in validation messages instead of the kind.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub kind: StepKind,
+    /// Layers the step reads, the ones of its kind when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Vec<StepLayer>>,
+    /// Layers the step writes, the ones of its kind when omitted. It cannot
+    /// leave out any of them, as the step writes them anyway.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Vec<StepLayer>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum StepKind {
+    /// Noise of `elevation_gen`.
+    Elevation,
+    /// Island mask: raises the centre of the map and lowers its corners by up
+    /// to `strength`.
+    Shape { strength: f64 },
+    /// Thermal erosion: where the slope to the lowest neighbour is steeper
+    /// than `talus`, part of the difference slides down, `iterations` times.
+    Erosion { iterations: u32, talus: f64 },
+    /// Latitude and noise of `temperature_gen`.
+    Temperature,
+    /// Biome classification from the other layers, it must be the last step.
+    Biomes,
+}
+
+impl StepKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StepKind::Elevation => "elevation",
+            StepKind::Shape { .. } => "shape",
+            StepKind::Erosion { .. } => "erosion",
+            StepKind::Temperature => "temperature",
+            StepKind::Biomes => "biomes",
+        }
+    }
+
+    /// Layers the step cannot run without.
+    pub fn reads(&self) -> &'static [StepLayer] {
+        match self {
+            StepKind::Shape { .. } | StepKind::Erosion { .. } => &[StepLayer::Elevation],
+            StepKind::Elevation | StepKind::Temperature | StepKind::Biomes => &[],
+        }
+    }
+
+    pub fn writes(&self) -> &'static [StepLayer] {
+        match self {
+            StepKind::Elevation | StepKind::Shape { .. } | StepKind::Erosion { .. } => {
+                &[StepLayer::Elevation]
+            }
+            StepKind::Temperature => &[StepLayer::Temperature],
+            StepKind::Biomes => &[StepLayer::Biome],
+        }
+    }
+}
+
+impl Step {
+    pub fn new(kind: StepKind) -> Self {
+        Self {
+            name: None,
+            kind,
+            inputs: None,
+            outputs: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.kind.name())
+    }
+
+    pub fn inputs(&self) -> &[StepLayer] {
+        match (&self.inputs, &self.kind) {
+            (Some(inputs), _) => inputs,
+            // Biome conditions may use any layer
+            (None, StepKind::Biomes) => &[StepLayer::Elevation, StepLayer::Temperature],
+            (None, kind) => kind.reads(),
+        }
+    }
+
+    pub fn outputs(&self) -> &[StepLayer] {
+        self.outputs.as_deref().unwrap_or(self.kind.writes())
+    }
+}
+
+/// Noise elevation and temperature, then the biomes, like before steps.
+pub fn default_steps() -> Vec<Step> {
+    [StepKind::Elevation, StepKind::Temperature, StepKind::Biomes]
+        .into_iter()
+        .map(Step::new)
+        .collect()
+}
+
+/// A problem of the step at `index`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepError {
+    pub index: usize,
+    pub message: String,
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Step {}: {}", self.index + 1, self.message)
+    }
+}
+
+/// Checks that every step declares what it reads and writes, that it outputs
+/// exactly what its kind writes, and that its inputs are the outputs of
+/// previous steps.
+pub fn validate_steps(steps: &[Step]) -> Vec<StepError> {
+    let mut errors = Vec::new();
+    let mut available: Vec<StepLayer> = Vec::new();
+
+    for (index, step) in steps.iter().enumerate() {
+        let mut error = |message: String| {
+            errors.push(StepError {
+                index,
+                message: format!("`{}` {}", step.name(), message),
+            })
+        };
+
+        for layer in step.kind.reads() {
+            if !step.inputs().contains(layer) {
+                error(format!("reads `{}` but does not declare it", layer));
+            }
+        }
+        for layer in step.outputs() {
+            if !step.kind.writes().contains(layer) {
+                error(format!("cannot output `{}`", layer));
+            }
+        }
+        for layer in step.kind.writes() {
+            if !step.outputs().contains(layer) {
+                error(format!("writes `{}` but does not declare it", layer));
+            }
+        }
+        for layer in step.inputs() {
+            if !available.contains(layer) {
+                error(format!("needs `{}`, which no previous step outputs", layer));
+            }
+        }
+        if step.kind == StepKind::Biomes && index + 1 != steps.len() {
+            error("must be the last step".to_string());
+        }
+
+        available.extend(step.outputs());
+    }
+
+    errors
+}
+
+pub fn apply_shape(world: &mut World, settings: &Settings, strength: f64) {
+    for index in 0..world.elevation.len() {
+        let tile_pos = world.tile_pos(index);
+        world.elevation[index] -=
+            strength * CircleCenteredShape.generate(tile_pos.x, tile_pos.y, settings);
+    }
+}
+
+/// Advances `progress` by the tiles of each iteration, and stops between
+/// iterations once it is cancelled.
+pub fn erode(
+    world: &mut World,
+    settings: &Settings,
+    iterations: u32,
+    talus: f64,
+    progress: &GenerationProgress,
+) -> Result<(), Cancelled> {
+    let map_size = TilemapSize {
+        x: world.width,
+        y: world.height,
+    };
+
+    for _ in 0..iterations {
+        progress.check()?;
+        let mut delta = vec![0.; world.elevation.len()];
+        for (index, &elevation) in world.elevation.iter().enumerate() {
+            let lowest = hex::neighbours(&world.tile_pos(index), &map_size, settings.wrap)
+                .map(|neighbour| world.index(&neighbour))
+                .min_by(|&a, &b| world.elevation[a].total_cmp(&world.elevation[b]));
+            let Some(lowest) = lowest else {
+                continue;
+            };
+            let slope = elevation - world.elevation[lowest];
+            if slope > talus {
+                // A quarter so that tiles sharing a neighbour do not overshoot
+                let moved = (slope - talus) / 4.;
+                delta[index] -= moved;
+                delta[lowest] += moved;
+            }
+        }
+        for (elevation, delta) in world.elevation.iter_mut().zip(delta) {
+            *elevation += delta;
+        }
+        progress.advance(world.elevation.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::worlds::map::generator::world::{Cancelled, GenerationProgress, World};
+    use crate::worlds::settings::Settings;
+
+    use super::{default_steps, erode, validate_steps, Step, StepKind, StepLayer};
+
+    #[rstest]
+    fn test_default_steps_are_valid() {
+        assert_eq!(validate_steps(&default_steps()), []);
+    }
+
+    #[rstest]
+    #[case::missing_dependency(
+        vec![Step::new(StepKind::Erosion { iterations: 1, talus: 0.1 })],
+        "`erosion` needs `elevation`, which no previous step outputs"
+    )]
+    #[case::undeclared_input(
+        vec![
+            Step::new(StepKind::Elevation),
+            Step { inputs: Some(vec![]), ..Step::new(StepKind::Shape { strength: 1. }) },
+        ],
+        "`shape` reads `elevation` but does not declare it"
+    )]
+    #[case::wrong_output(
+        vec![Step {
+            outputs: Some(vec![StepLayer::Temperature, StepLayer::Biome]),
+            ..Step::new(StepKind::Temperature)
+        }],
+        "`temperature` cannot output `biome`"
+    )]
+    #[case::undeclared_output(
+        vec![
+            Step::new(StepKind::Elevation),
+            Step {
+                outputs: Some(vec![]),
+                ..Step::new(StepKind::Erosion { iterations: 1, talus: 0.1 })
+            },
+        ],
+        "`erosion` writes `elevation` but does not declare it"
+    )]
+    #[case::biomes_not_last(
+        vec![
+            Step::new(StepKind::Elevation),
+            Step { inputs: Some(vec![StepLayer::Elevation]), ..Step::new(StepKind::Biomes) },
+            Step::new(StepKind::Temperature),
+        ],
+        "`biomes` must be the last step"
+    )]
+    fn test_invalid_steps(#[case] steps: Vec<Step>, #[case] message: &str) {
+        let errors = validate_steps(&steps);
+
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].message, message);
+    }
+
+    #[rstest]
+    fn test_erosion_flattens_peaks() {
+        let settings = Settings {
+            width: 5,
+            height: 5,
+            ..Settings::from_seed(0)
+        };
+        let mut world = World {
+            width: 5,
+            height: 5,
+            elevation: vec![0.; 25],
+            ..World::default()
+        };
+        world.elevation[12] = 1.;
+
+        let progress = GenerationProgress::default();
+        erode(&mut world, &settings, 3, 0.1, &progress).unwrap();
+
+        assert!(world.elevation[12] < 1.);
+        assert!(world
+            .elevation
+            .iter()
+            .all(|&elevation| elevation <= world.elevation[12]));
+        let total: f64 = world.elevation.iter().sum();
+        assert!((total - 1.).abs() < 1e-9, "erosion moves material around");
+    }
+
+    #[rstest]
+    fn test_erosion_stops_when_cancelled() {
+        let settings = Settings::from_seed(0);
+        let mut world = World {
+            width: 5,
+            height: 5,
+            elevation: vec![0.; 25],
+            ..World::default()
+        };
+        world.elevation[12] = 1.;
+        let progress = GenerationProgress::default();
+        progress.cancel();
+
+        let result = erode(&mut world, &settings, 3, 0.1, &progress);
+
+        assert_eq!(result, Err(Cancelled));
+        assert_eq!(world.elevation[12], 1.);
+    }
+}
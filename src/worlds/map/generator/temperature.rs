@@ -1,48 +1,55 @@
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::{TilePos, TileStorage};
-use noise::{NoiseFn, Perlin};
 
 use crate::worlds::{
-    map::{MapGenerator, MAX_PERLIN_SCALE},
+    map::{generator::NoiseLayer, MapGenerator},
+    seed::SeedStream,
     settings::Settings,
     utils::xy_to_lonlat,
 };
 
-#[derive(Component, Default, Debug)]
+/// Temperature of a tile, the values of `LayerGrid<TileTemperature>`.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub struct TileTemperature(pub f64);
 
-pub struct TemperatureGenerator;
-
-impl MapGenerator for TemperatureGenerator {
-    fn get_value(&self, tile_pos: &TilePos, settings: &Settings) -> f64 {
-        let mut value = 0.;
-
-        let (lon, lat) = xy_to_lonlat(settings, tile_pos.x, tile_pos.y);
-
-        let scale = settings
-            .temperature_gen
-            .perlin
-            .noise_scale
-            .clamp(0., MAX_PERLIN_SCALE);
-        let perlin = Perlin::new(settings.temperature_gen.perlin.seed);
-
-        for o in 0..settings.temperature_gen.perlin.octaves {
-            let offset_x: f64 = settings.temperature_gen.perlin.offset.x as f64;
-            let offset_y: f64 = settings.temperature_gen.perlin.offset.y as f64;
-            let frequency: f64 = settings.temperature_gen.perlin.lacunarity.powi(o);
-            let amplitude: f64 = settings.temperature_gen.perlin.persistance.powi(o);
-            let sample_x = tile_pos.x as f64 / scale * frequency + offset_x;
-            let sample_y = tile_pos.y as f64 / scale * frequency + offset_y;
+/// Holds the noise of the temperature, built once for all the tiles of a
+/// world.
+pub struct TemperatureGenerator {
+    noise: NoiseLayer,
+}
 
-            let perlin_value = perlin.get([sample_x, sample_y, 0.0]);
-            value += perlin_value * amplitude;
+impl TemperatureGenerator {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            noise: NoiseLayer::new(
+                settings.world_seed,
+                SeedStream::Temperature,
+                &settings.temperature_gen.perlin,
+            ),
         }
+    }
+
+    pub fn noise(&self) -> &NoiseLayer {
+        &self.noise
+    }
 
+    /// Temperature of a noise value of [`TemperatureGenerator::noise`] at a
+    /// latitude.
+    pub fn from_noise(value: f64, lat: f64, settings: &Settings) -> f64 {
         let lat_factor = (lat.to_radians().cos() * settings.temperature_gen.scale_lat_factor);
         let noise_factor = (value + 1.) * settings.temperature_gen.noise_factor;
 
         lat_factor + noise_factor - 10.
     }
+}
+
+impl MapGenerator for TemperatureGenerator {
+    fn get_value(&self, tile_pos: &TilePos, settings: &Settings) -> f64 {
+        let (lon, lat) = xy_to_lonlat(settings, tile_pos.x, tile_pos.y);
+
+        let value = self.noise.at_tile(tile_pos, settings);
+        Self::from_noise(value, lat, settings)
+    }
 
     fn get_min_max(settings: &Settings) -> [f64; 2] {
         let min_lat_factor =
@@ -58,19 +65,3 @@ impl MapGenerator for TemperatureGenerator {
         [min_temperature, max_temperature]
     }
 }
-
-pub fn generate(
-    mut commands: Commands,
-    settings: Res<Settings>,
-    mut tilemap_query: Query<&TileStorage>,
-    tile_query: Query<&TilePos>,
-) {
-    let generator = TemperatureGenerator;
-    for tile_storage in tilemap_query.iter_mut() {
-        for tile_pos in tile_query.iter() {
-            commands
-                .entity(tile_storage.get(tile_pos).unwrap())
-                .insert(TileTemperature(generator.get_value(tile_pos, &settings)));
-        }
-    }
-}
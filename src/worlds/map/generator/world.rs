@@ -0,0 +1,728 @@
+use std::fmt;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use bevy::math::IVec2;
+use bevy::tasks::{ComputeTaskPool, ParallelSlice, TaskPool};
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::worlds::{
+    map::biomes::Biome,
+    settings::Settings,
+    sphere::{from_lonlat, to_lonlat, SphereGrid, MAX_SPHERE_SUBDIVISIONS},
+    utils::{position_to_lonlat, tile_area_weight, tile_lonlat, xy_to_lonlat},
+};
+
+use super::{
+    biome::{BiomeClassifier, BiomeId},
+    elevation::ElevationGenerator,
+    steps::{apply_shape, erode, StepKind},
+    temperature::TemperatureGenerator,
+    MapGenerator,
+};
+
+/// Rows of the map generated by each parallel task.
+const CHUNK_ROWS: usize = 8;
+/// Cells of the sphere grid generated by each parallel task.
+const CHUNK_CELLS: usize = 2048;
+
+/// Side of the chunks of streamed worlds, in tiles. Even, so that rows keep
+/// their hexagon offset from one chunk to the next.
+pub const CHUNK_SIZE: u32 = 64;
+
+/// Generated layers of a world, stored row by row like `TilePos::to_index`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct World {
+    pub width: u32,
+    pub height: u32,
+    pub elevation: Vec<f64>,
+    pub temperature: Vec<f64>,
+    pub biome: Vec<Option<BiomeId>>,
+    /// Biome names, indexed by [`BiomeId`].
+    pub biome_names: Vec<String>,
+}
+
+impl World {
+    pub fn index(&self, tile_pos: &TilePos) -> usize {
+        tile_pos.y as usize * self.width as usize + tile_pos.x as usize
+    }
+
+    pub fn tile_pos(&self, index: usize) -> TilePos {
+        TilePos {
+            x: (index % self.width as usize) as u32,
+            y: (index / self.width as usize) as u32,
+        }
+    }
+
+    pub fn biome_name(&self, tile_pos: &TilePos) -> Option<&str> {
+        self.biome[self.index(tile_pos)].map(|id| self.biome_names[id as usize].as_str())
+    }
+
+    /// Share of the globe covered by each biome, indexed by [`BiomeId`]. Tiles
+    /// are weighted by the area they cover with the projection of the settings.
+    pub fn biome_shares(&self, settings: &Settings) -> Vec<f64> {
+        let mut shares = vec![0.; self.biome_names.len()];
+        let mut total = 0.;
+        for (index, biome) in self.biome.iter().enumerate() {
+            let tile_pos = self.tile_pos(index);
+            let weight = tile_area_weight(settings, tile_pos.x, tile_pos.y);
+            if let Some(id) = biome {
+                shares[*id as usize] += weight;
+            }
+            total += weight;
+        }
+        if total > 0. {
+            shares.iter_mut().for_each(|share| *share /= total);
+        }
+        shares
+    }
+}
+
+/// Layers of a spherical world, indexed like the cells of its grid.
+pub struct SphereWorld {
+    pub grid: SphereGrid,
+    pub elevation: Vec<f64>,
+    pub temperature: Vec<f64>,
+}
+
+/// Returned by a generation stopped with [`GenerationProgress::cancel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Generation cancelled")
+    }
+}
+
+/// Shared with a running generation to follow it, or to stop it.
+#[derive(Debug, Default, Clone)]
+pub struct GenerationProgress {
+    done: Arc<AtomicUsize>,
+    total: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl GenerationProgress {
+    /// Done share of the running step, between 0 and 1.
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.;
+        }
+        (self.done.load(Ordering::Relaxed) as f32 / total as f32).min(1.)
+    }
+
+    /// The generation stops at its next chunk or step, and returns
+    /// [`Cancelled`].
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn check(&self) -> Result<(), Cancelled> {
+        match self.is_cancelled() {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    fn start(&self, total: usize) {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub(super) fn advance(&self, done: usize) {
+        self.done.fetch_add(done, Ordering::Relaxed);
+    }
+}
+
+/// Generates a [`World`] from settings alone, without any Bevy `App`.
+///
+/// Tiles are generated in chunks of rows in parallel on the
+/// [`ComputeTaskPool`].
+pub struct WorldGenerator {
+    settings: Settings,
+    classifier: BiomeClassifier,
+    progress: GenerationProgress,
+}
+
+impl WorldGenerator {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            classifier: BiomeClassifier::default(),
+            progress: GenerationProgress::default(),
+        }
+    }
+
+    pub fn with_biomes(mut self, biomes: impl IntoIterator<Item = Biome>) -> Self {
+        self.classifier = BiomeClassifier::new(biomes);
+        self
+    }
+
+    /// Reports to `progress`, and stops once it is cancelled.
+    pub fn with_progress(mut self, progress: GenerationProgress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Runs the steps of the settings: the layer steps, then the biome
+    /// classification.
+    pub fn generate(&self) -> Result<World, Cancelled> {
+        let mut world = self.generate_layers()?;
+        self.classify(&mut world)?;
+        Ok(world)
+    }
+
+    /// Runs the steps generating the elevation and temperature of the tiles,
+    /// noise is sampled on the map or, when `settings.sphere` is set, on the
+    /// sphere projected on the map. Tiles have no biome until
+    /// [`WorldGenerator::classify`].
+    pub fn generate_layers(&self) -> Result<World, Cancelled> {
+        let settings = &self.settings;
+        let count = settings.width as usize * settings.height as usize;
+        let mut world = World {
+            width: settings.width,
+            height: settings.height,
+            elevation: vec![0.; count],
+            temperature: vec![0.; count],
+            biome: vec![None; count],
+            biome_names: self.classifier.names(),
+        };
+        // Steps count once per tile they go through, noise on the sphere is
+        // sampled on its cells then projected once
+        let sphere_cells = settings
+            .sphere
+            .then(|| 10 * 4usize.pow(self.sphere_subdivisions()) + 2);
+        let passes: usize = settings
+            .steps
+            .iter()
+            .map(|step| match step.kind {
+                StepKind::Elevation | StepKind::Temperature => usize::from(!settings.sphere),
+                StepKind::Shape { .. } => 1,
+                StepKind::Erosion { iterations, .. } => iterations as usize,
+                StepKind::Biomes => 0,
+            })
+            .sum();
+        self.progress.start(match sphere_cells {
+            Some(cells) => cells + count + passes * count,
+            None => passes * count,
+        });
+        let sphere = match settings.sphere {
+            true => Some(self.project(&self.generate_sphere()?)?),
+            false => None,
+        };
+
+        for step in &settings.steps {
+            self.progress.check()?;
+            match step.kind {
+                StepKind::Elevation => {
+                    world.elevation = match &sphere {
+                        Some(sphere) => sphere.elevation.clone(),
+                        None => {
+                            let generator = ElevationGenerator::new(settings);
+                            self.tile_values(|tile_pos| generator.get_value(tile_pos, settings))?
+                        }
+                    }
+                }
+                StepKind::Temperature => {
+                    world.temperature = match &sphere {
+                        Some(sphere) => sphere.temperature.clone(),
+                        None => {
+                            let generator = TemperatureGenerator::new(settings);
+                            self.tile_values(|tile_pos| generator.get_value(tile_pos, settings))?
+                        }
+                    }
+                }
+                StepKind::Shape { strength } => {
+                    apply_shape(&mut world, settings, strength);
+                    self.progress.advance(count);
+                }
+                StepKind::Erosion { iterations, talus } => {
+                    erode(&mut world, settings, iterations, talus, &self.progress)?
+                }
+                StepKind::Biomes => {}
+            }
+        }
+
+        Ok(world)
+    }
+
+    /// Values of the tiles, row by row.
+    fn tile_values<T: Send + 'static>(
+        &self,
+        value: impl Fn(&TilePos) -> T + Sync,
+    ) -> Result<Vec<T>, Cancelled> {
+        let width = self.settings.width;
+        self.par_rows(|rows| {
+            rows.iter()
+                .flat_map(|&y| (0..width).map(move |x| TilePos { x, y }))
+                .map(|tile_pos| value(&tile_pos))
+                .collect()
+        })
+    }
+
+    /// Maps chunks of rows of the map in parallel, `chunk` returns the values
+    /// of the tiles of its rows.
+    fn par_rows<T: Send + 'static>(
+        &self,
+        chunk: impl Fn(&[u32]) -> Vec<T> + Sync,
+    ) -> Result<Vec<T>, Cancelled> {
+        let rows: Vec<u32> = (0..self.settings.height).collect();
+        self.par_chunks(&rows, CHUNK_ROWS, self.settings.width as usize, chunk)
+    }
+
+    /// Maps chunks of `items` in parallel and concatenates their values in
+    /// order, each item advancing the progress by `cost`. Once cancelled, the
+    /// remaining chunks are skipped and no values are returned.
+    fn par_chunks<I: Sync, T: Send + 'static>(
+        &self,
+        items: &[I],
+        chunk_size: usize,
+        cost: usize,
+        chunk: impl Fn(&[I]) -> Vec<T> + Sync,
+    ) -> Result<Vec<T>, Cancelled> {
+        let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        let chunks = items.par_chunk_map(pool, chunk_size, |_, items| {
+            self.progress.check()?;
+            let values = chunk(items);
+            self.progress.advance(items.len() * cost);
+            Ok(values)
+        });
+        chunks
+            .into_iter()
+            .try_fold(Vec::new(), |mut values, chunk| {
+                values.extend(chunk?);
+                Ok(values)
+            })
+    }
+
+    /// Classifies the biome of every tile from its layers and latitude, tiles
+    /// out of the projected globe, or of steps without classification, have
+    /// no biome. A cancelled classification leaves `world` unchanged.
+    pub fn classify(&self, world: &mut World) -> Result<(), Cancelled> {
+        let settings = &self.settings;
+        if !settings
+            .steps
+            .iter()
+            .any(|step| step.kind == StepKind::Biomes)
+        {
+            world.biome = vec![None; world.elevation.len()];
+            world.biome_names = self.classifier.names();
+            return Ok(());
+        }
+
+        self.progress.start(world.elevation.len());
+        let layers: &World = world;
+        let biome = self.tile_values(|tile_pos| {
+            let index = layers.index(tile_pos);
+            let (_, latitude) = xy_to_lonlat(settings, tile_pos.x, tile_pos.y);
+            let on_globe = tile_lonlat(settings, tile_pos.x, tile_pos.y).is_some();
+            on_globe
+                .then(|| {
+                    self.classifier.classify(
+                        layers.elevation[index],
+                        layers.temperature[index],
+                        latitude,
+                    )
+                })
+                .flatten()
+        })?;
+
+        world.biome = biome;
+        world.biome_names = self.classifier.names();
+        Ok(())
+    }
+
+    /// Generates a chunk of an unbounded world, its first tile at
+    /// `chunk * CHUNK_SIZE`. Noise only depends on the position of the tiles
+    /// so that chunks stitch seamlessly, and rows beyond the height of the
+    /// settings keep the latitude of the nearest pole.
+    ///
+    /// Only the noise and biome steps run: shape and erosion need the whole
+    /// map.
+    pub fn generate_chunk(&self, chunk: IVec2) -> World {
+        let settings = &self.settings;
+        let elevation_generator = ElevationGenerator::new(settings);
+        let temperature_generator = TemperatureGenerator::new(settings);
+        let has_step = |kind: StepKind| settings.steps.iter().any(|step| step.kind == kind);
+        let count = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        let mut world = World {
+            width: CHUNK_SIZE,
+            height: CHUNK_SIZE,
+            elevation: vec![0.; count],
+            temperature: vec![0.; count],
+            biome: vec![None; count],
+            biome_names: self.classifier.names(),
+        };
+
+        for index in 0..count {
+            let tile_pos = world.tile_pos(index);
+            let x = (chunk.x * CHUNK_SIZE as i32 + tile_pos.x as i32) as f64;
+            let y = (chunk.y * CHUNK_SIZE as i32 + tile_pos.y as i32) as f64;
+            let (_, latitude) = position_to_lonlat(settings, x, y);
+
+            if has_step(StepKind::Elevation) {
+                world.elevation[index] =
+                    ElevationGenerator::from_noise(elevation_generator.noise().at_position(x, y));
+            }
+            if has_step(StepKind::Temperature) {
+                world.temperature[index] = TemperatureGenerator::from_noise(
+                    temperature_generator.noise().at_position(x, y),
+                    latitude,
+                    settings,
+                );
+            }
+            if has_step(StepKind::Biomes) {
+                world.biome[index] = self.classifier.classify(
+                    world.elevation[index],
+                    world.temperature[index],
+                    latitude,
+                );
+            }
+        }
+
+        world
+    }
+
+    /// Subdivisions of the sphere grid, bounded so that settings skipping
+    /// validation (world codes, the inspector) cannot exhaust the memory.
+    fn sphere_subdivisions(&self) -> u32 {
+        self.settings
+            .sphere_subdivisions
+            .min(MAX_SPHERE_SUBDIVISIONS)
+    }
+
+    /// Generates the elevation and temperature on every cell of the sphere
+    /// grid, the noise is sampled in 3D so there are neither seams nor
+    /// distortion at the poles. Biomes are classified on the projected map.
+    pub fn generate_sphere(&self) -> Result<SphereWorld, Cancelled> {
+        let settings = &self.settings;
+        let grid = SphereGrid::new(self.sphere_subdivisions());
+        let elevation_generator = ElevationGenerator::new(settings);
+        let temperature_generator = TemperatureGenerator::new(settings);
+
+        let cells = self.par_chunks(&grid.cells, CHUNK_CELLS, 1, |cells| {
+            cells
+                .iter()
+                .map(|&position| {
+                    let (_, latitude) = to_lonlat(position);
+                    let elevation = ElevationGenerator::from_noise(
+                        elevation_generator.noise().at_sphere(position, settings),
+                    );
+                    let temperature = TemperatureGenerator::from_noise(
+                        temperature_generator.noise().at_sphere(position, settings),
+                        latitude,
+                        settings,
+                    );
+                    (elevation, temperature)
+                })
+                .collect()
+        })?;
+
+        let mut world = SphereWorld {
+            elevation: Vec::with_capacity(grid.len()),
+            temperature: Vec::with_capacity(grid.len()),
+            grid,
+        };
+        for (elevation, temperature) in cells {
+            world.elevation.push(elevation);
+            world.temperature.push(temperature);
+        }
+
+        Ok(world)
+    }
+
+    /// Flattens a spherical world on the map with the projection of the
+    /// settings: each tile takes the elevation and temperature of the cell at
+    /// its longitude and latitude, biomes are left to [`WorldGenerator::classify`].
+    pub fn project(&self, sphere: &SphereWorld) -> Result<World, Cancelled> {
+        let settings = &self.settings;
+        let count = settings.width as usize * settings.height as usize;
+
+        // Neighbouring tiles are in the same or neighbouring cells, start from
+        // the previous tile, or from the first of the previous row at the
+        // start of a row.
+        let cells = self.par_rows(|rows| {
+            let mut cells = Vec::with_capacity(rows.len() * settings.width as usize);
+            let mut row_start = 0;
+            for &y in rows {
+                let mut cell = row_start;
+                for x in 0..settings.width {
+                    let (lon, lat) = xy_to_lonlat(settings, x, y);
+                    cell = sphere.grid.nearest(from_lonlat(lon, lat), cell);
+                    if x == 0 {
+                        row_start = cell;
+                    }
+                    cells.push(cell);
+                }
+            }
+            cells
+        })?;
+
+        Ok(World {
+            width: settings.width,
+            height: settings.height,
+            elevation: cells.iter().map(|&cell| sphere.elevation[cell]).collect(),
+            temperature: cells.iter().map(|&cell| sphere.temperature[cell]).collect(),
+            biome: vec![None; count],
+            biome_names: self.classifier.names(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::math::IVec2;
+    use bevy_ecs_tilemap::tiles::TilePos;
+    use rstest::rstest;
+
+    use crate::worlds::{
+        map::{
+            biomes::{load_biomes, BIOMES_PATH},
+            generator::{
+                elevation::ElevationGenerator,
+                steps::{Step, StepKind},
+                MapGenerator,
+            },
+        },
+        projection::Projection,
+        settings::Settings,
+        sphere::MAX_SPHERE_SUBDIVISIONS,
+    };
+
+    use std::sync::atomic::Ordering;
+
+    use super::{Cancelled, GenerationProgress, World, WorldGenerator, CHUNK_SIZE};
+
+    fn small_settings() -> Settings {
+        Settings {
+            width: 24,
+            height: 16,
+            ..Settings::from_seed(1234)
+        }
+    }
+
+    #[rstest]
+    fn test_generate_dense_layers() {
+        let settings = small_settings();
+        let world = WorldGenerator::new(settings.clone()).generate().unwrap();
+        let count = settings.width as usize * settings.height as usize;
+
+        assert_eq!(world.elevation.len(), count);
+        assert_eq!(world.temperature.len(), count);
+        assert_eq!(world.biome.len(), count);
+
+        let tile_pos = TilePos { x: 17, y: 3 };
+        assert_eq!(world.tile_pos(world.index(&tile_pos)), tile_pos);
+        assert_eq!(
+            world.elevation[world.index(&tile_pos)],
+            ElevationGenerator::new(&settings).get_value(&tile_pos, &settings)
+        );
+    }
+
+    #[rstest]
+    fn test_index_of_large_maps() {
+        // More tiles than a `u32` counts
+        let world = World {
+            width: 1 << 20,
+            height: 1 << 20,
+            ..World::default()
+        };
+        let last = TilePos {
+            x: (1 << 20) - 1,
+            y: (1 << 20) - 1,
+        };
+
+        assert_eq!(world.index(&last), (1usize << 40) - 1);
+        assert_eq!(world.tile_pos((1usize << 40) - 1), last);
+    }
+
+    #[rstest]
+    fn test_generate_is_deterministic() {
+        let a = WorldGenerator::new(small_settings()).generate().unwrap();
+        let b = WorldGenerator::new(small_settings()).generate().unwrap();
+
+        assert_eq!(a, b);
+    }
+
+    #[rstest]
+    fn test_generation_progress() {
+        let progress = GenerationProgress::default();
+        let generator = WorldGenerator::new(small_settings()).with_progress(progress.clone());
+
+        assert_eq!(
+            generator.generate_layers(),
+            WorldGenerator::new(small_settings()).generate_layers()
+        );
+        assert_eq!(progress.fraction(), 1.);
+
+        progress.cancel();
+        assert_eq!(generator.generate_layers(), Err(Cancelled));
+        let mut world = World::default();
+        assert_eq!(generator.classify(&mut world), Err(Cancelled));
+        assert_eq!(world, World::default());
+    }
+
+    #[rstest]
+    fn test_progress_counts_every_step() {
+        let settings = Settings {
+            steps: [
+                StepKind::Elevation,
+                StepKind::Temperature,
+                StepKind::Shape { strength: 0.5 },
+                StepKind::Erosion {
+                    iterations: 3,
+                    talus: 0.1,
+                },
+                StepKind::Biomes,
+            ]
+            .into_iter()
+            .map(Step::new)
+            .collect(),
+            ..small_settings()
+        };
+        let count = settings.width as usize * settings.height as usize;
+        let progress = GenerationProgress::default();
+        let generator = WorldGenerator::new(settings).with_progress(progress.clone());
+
+        generator.generate_layers().unwrap();
+
+        // Two noise steps, the shape, then three erosion iterations
+        assert_eq!(progress.done.load(Ordering::Relaxed), 6 * count);
+        assert_eq!(progress.fraction(), 1.);
+    }
+
+    #[rstest]
+    fn test_chunks_match_the_map() {
+        let biomes = load_biomes(Path::new(BIOMES_PATH)).unwrap();
+        let settings = Settings {
+            width: CHUNK_SIZE + 8,
+            height: CHUNK_SIZE + 4,
+            ..Settings::from_seed(7)
+        };
+        let generator = WorldGenerator::new(settings).with_biomes(biomes.into_values());
+        let world = generator.generate().unwrap();
+        let chunk = generator.generate_chunk(IVec2::ZERO);
+
+        for index in 0..chunk.elevation.len() {
+            let tile_pos = chunk.tile_pos(index);
+            let map_index = world.index(&tile_pos);
+            assert_eq!(chunk.elevation[index], world.elevation[map_index]);
+            assert_eq!(chunk.temperature[index], world.temperature[map_index]);
+            assert_eq!(chunk.biome[index], world.biome[map_index]);
+        }
+        // Chunks go on beyond the map, on every side
+        let west = generator.generate_chunk(IVec2::new(-1, 3));
+        assert_ne!(west.elevation, chunk.elevation);
+    }
+
+    #[rstest]
+    fn test_sphere_subdivisions_are_bounded() {
+        let generator = WorldGenerator::new(Settings {
+            sphere_subdivisions: 32,
+            ..small_settings()
+        });
+
+        assert_eq!(generator.sphere_subdivisions(), MAX_SPHERE_SUBDIVISIONS);
+    }
+
+    #[rstest]
+    fn test_generate_on_sphere() {
+        let settings = Settings {
+            sphere: true,
+            sphere_subdivisions: 3,
+            ..small_settings()
+        };
+        let generator = WorldGenerator::new(settings.clone());
+        let sphere = generator.generate_sphere().unwrap();
+        let world = generator.generate().unwrap();
+
+        assert_eq!(sphere.elevation.len(), sphere.grid.len());
+        assert_eq!(
+            world.elevation.len(),
+            settings.width as usize * settings.height as usize
+        );
+        // Every tile of the bottom row is on the south pole, in the same cell
+        let pole = world.elevation[0];
+        assert!((0..settings.width as usize).all(|x| world.elevation[x] == pole));
+        // The east and west edges are the same meridian
+        for y in 0..settings.height {
+            let west = world.index(&TilePos { x: 0, y });
+            let east = world.index(&TilePos {
+                x: settings.width - 1,
+                y,
+            });
+            assert_eq!(world.elevation[west], world.elevation[east]);
+        }
+    }
+
+    #[rstest]
+    fn test_generate_classifies_shipped_biomes() {
+        let biomes = load_biomes(Path::new(BIOMES_PATH)).unwrap();
+        let world = WorldGenerator::new(small_settings())
+            .with_biomes(biomes.into_values())
+            .generate()
+            .unwrap();
+
+        assert_eq!(world.biome_names, ["Land", "Ocean"]);
+        for (index, &elevation) in world.elevation.iter().enumerate() {
+            let expected = if elevation >= 0. { "Land" } else { "Ocean" };
+            assert_eq!(world.biome_name(&world.tile_pos(index)), Some(expected));
+        }
+    }
+
+    #[rstest]
+    fn test_biome_shares_are_area_weighted() {
+        let settings = Settings {
+            width: 4,
+            height: 5,
+            ..Settings::from_seed(0)
+        };
+        let mut world = World {
+            width: 4,
+            height: 5,
+            biome_names: vec!["Polar".to_string(), "Temperate".to_string()],
+            ..World::default()
+        };
+        for y in 0..5 {
+            world.biome.extend([Some(if y == 2 { 1 } else { 0 }); 4]);
+        }
+
+        // Rows at 90° weigh nothing, rows at 45° weigh cos 45° of the equator
+        let shares = world.biome_shares(&settings);
+        let polar = 2. * 45f64.to_radians().cos();
+        assert!((shares[0] - polar / (polar + 1.)).abs() < 1e-9);
+        assert!((shares[0] + shares[1] - 1.).abs() < 1e-9);
+    }
+
+    #[rstest]
+    fn test_tiles_out_of_projection_have_no_biome() {
+        let biomes = load_biomes(Path::new(BIOMES_PATH)).unwrap();
+        let settings = Settings {
+            projection: Projection::Mollweide,
+            ..small_settings()
+        };
+        let world = WorldGenerator::new(settings.clone())
+            .with_biomes(biomes.into_values())
+            .generate()
+            .unwrap();
+
+        assert_eq!(world.biome_name(&TilePos { x: 0, y: 0 }), None);
+        assert!(world.biome_name(&TilePos { x: 12, y: 8 }).is_some());
+        let shares: f64 = world.biome_shares(&settings).iter().sum();
+        assert!((shares - 1.).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,227 @@
+//! Tile under the cursor, and the one pinned by clicking it, with the values of
+//! their layers for the "Tile" window.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::worlds::{settings::Settings, utils::xy_to_lonlat};
+
+use super::{
+    generator::{biome::TileBiome, elevation::TileElevation, temperature::TileTemperature},
+    layers::LayerGrid,
+    wrap::WrapCopy,
+    MapTilemap,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<InspectedTile>().add_systems(
+        Update,
+        (hover_tile, pin_tile)
+            .chain()
+            .run_if(|settings: Res<Settings>| !settings.streaming),
+    );
+}
+
+/// Tile under the cursor and pinned tile, clicking a tile pins it and
+/// clicking it again unpins it.
+#[derive(Resource, Debug, Default)]
+pub struct InspectedTile {
+    pub hovered: Option<TilePos>,
+    pub pinned: Option<TilePos>,
+    /// Hovered when the button was pressed, so that dragging the camera does
+    /// not pin a tile.
+    pressed: Option<TilePos>,
+}
+
+/// Values of the layers at a tile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileInspection {
+    pub tile_pos: TilePos,
+    pub lonlat: (f64, f64),
+    pub elevation: TileElevation,
+    pub temperature: TileTemperature,
+    pub biome: Option<String>,
+}
+
+/// Values of the layers at a tile, `None` out of the map.
+pub fn inspect_tile(
+    settings: &Settings,
+    elevation: &LayerGrid<TileElevation>,
+    temperature: &LayerGrid<TileTemperature>,
+    biome: &LayerGrid<TileBiome>,
+    biome_names: &[String],
+    tile_pos: TilePos,
+) -> Option<TileInspection> {
+    Some(TileInspection {
+        tile_pos,
+        lonlat: xy_to_lonlat(settings, tile_pos.x, tile_pos.y),
+        elevation: *elevation.get(&tile_pos)?,
+        temperature: *temperature.get(&tile_pos)?,
+        biome: biome
+            .get(&tile_pos)
+            .and_then(|tile_biome| tile_biome.0)
+            .and_then(|id| biome_names.get(id as usize).cloned()),
+    })
+}
+
+type TilemapGeometry<'a> = (
+    &'a TilemapSize,
+    &'a TilemapGridSize,
+    &'a TilemapType,
+    &'a GlobalTransform,
+);
+
+/// Converts the cursor to a tile of the tilemap or of its wrap copy. The
+/// tilemap is used even when hidden, the image and the summaries covering it.
+fn hover_tile(
+    mut inspected: ResMut<InspectedTile>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    tilemap_query: Query<TilemapGeometry, Or<(With<MapTilemap>, With<WrapCopy>)>>,
+) {
+    let cursor = window_query
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position());
+    let hovered = cursor.and_then(|cursor| {
+        camera_query.iter().find_map(|(camera, camera_transform)| {
+            let position = camera.viewport_to_world_2d(camera_transform, cursor)?;
+            tilemap_query
+                .iter()
+                .find_map(|geometry| tile_at(position, geometry))
+        })
+    });
+    if inspected.hovered != hovered {
+        inspected.hovered = hovered;
+    }
+}
+
+/// Tile of a tilemap at a world position, `None` out of the map.
+fn tile_at(
+    position: Vec2,
+    (size, grid_size, map_type, transform): TilemapGeometry,
+) -> Option<TilePos> {
+    // In the frame of the tilemap
+    let position = transform
+        .affine()
+        .inverse()
+        .transform_point3(position.extend(0.))
+        .truncate();
+    TilePos::from_world_pos(&position, size, grid_size, map_type)
+}
+
+fn pin_tile(mut inspected: ResMut<InspectedTile>, mouse: Res<ButtonInput<MouseButton>>) {
+    if mouse.just_pressed(MouseButton::Left) {
+        inspected.pressed = inspected.hovered;
+    }
+    if mouse.just_released(MouseButton::Left) {
+        let pressed = inspected.pressed.take();
+        if pressed.is_some() && pressed == inspected.hovered {
+            inspected.pinned = if inspected.pinned == pressed {
+                None
+            } else {
+                pressed
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use bevy_ecs_tilemap::prelude::*;
+    use rstest::rstest;
+
+    use crate::worlds::{
+        map::{
+            generator::{biome::TileBiome, elevation::TileElevation, temperature::TileTemperature},
+            layers::LayerGrid,
+        },
+        settings::Settings,
+        utils::xy_to_lonlat,
+    };
+
+    use super::{inspect_tile, tile_at, TileInspection};
+
+    const GRID_SIZE: TilemapGridSize = TilemapGridSize { x: 16., y: 18. };
+
+    #[rstest]
+    #[case::even_row(TilePos { x: 2, y: 2 }, Vec2::ZERO, Some(TilePos { x: 2, y: 2 }))]
+    #[case::odd_row(TilePos { x: 3, y: 1 }, Vec2::ZERO, Some(TilePos { x: 3, y: 1 }))]
+    #[case::inside_edge(TilePos { x: 2, y: 2 }, Vec2::new(7., 0.), Some(TilePos { x: 2, y: 2 }))]
+    #[case::across_edge(TilePos { x: 2, y: 2 }, Vec2::new(9., 0.), Some(TilePos { x: 3, y: 2 }))]
+    #[case::across_odd_edge(TilePos { x: 3, y: 1 }, Vec2::new(9., 0.), Some(TilePos { x: 4, y: 1 }))]
+    #[case::left_of_map(TilePos { x: 0, y: 0 }, Vec2::new(-16., 0.), None)]
+    #[case::above_map(TilePos { x: 5, y: 4 }, Vec2::new(0., 18.), None)]
+    fn test_tile_at(
+        #[case] tile_pos: TilePos,
+        #[case] offset: Vec2,
+        #[case] expected: Option<TilePos>,
+    ) {
+        let size = TilemapSize { x: 6, y: 5 };
+        let map_type = TilemapType::Hexagon(HexCoordSystem::RowEven);
+        let transform = GlobalTransform::from(
+            Transform::from_xyz(100., -50., 0.).with_scale(Vec3::new(2., 2., 1.)),
+        );
+        // Offsets are in the frame of the tilemap, in which tiles are 16 wide
+        let local = tile_pos.center_in_world(&GRID_SIZE, &map_type) + offset;
+        let position = transform.transform_point(local.extend(0.)).truncate();
+
+        assert_eq!(
+            tile_at(position, (&size, &GRID_SIZE, &map_type, &transform)),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(TilePos { x: 1, y: 0 }, Some("plains"))]
+    #[case(TilePos { x: 0, y: 1 }, Some("ocean"))]
+    #[case(TilePos { x: 0, y: 0 }, None)]
+    fn test_inspect_tile(#[case] tile_pos: TilePos, #[case] biome_name: Option<&str>) {
+        let settings = Settings {
+            width: 2,
+            height: 2,
+            ..Settings::from_seed(0)
+        };
+        let values: Vec<f64> = vec![0., 1., 2., 3.];
+        let elevation = LayerGrid::new(2, 2, values.iter().copied().map(TileElevation).collect());
+        let temperature = LayerGrid::new(
+            2,
+            2,
+            values.iter().map(|value| TileTemperature(-value)).collect(),
+        );
+        let biome = LayerGrid::new(
+            2,
+            2,
+            [None, Some(0), Some(1), Some(0)]
+                .into_iter()
+                .map(TileBiome)
+                .collect(),
+        );
+        let biome_names = vec!["plains".to_string(), "ocean".to_string()];
+
+        let inspect = |tile_pos| {
+            inspect_tile(
+                &settings,
+                &elevation,
+                &temperature,
+                &biome,
+                &biome_names,
+                tile_pos,
+            )
+        };
+
+        let value = values[(tile_pos.y * 2 + tile_pos.x) as usize];
+        assert_eq!(
+            inspect(tile_pos),
+            Some(TileInspection {
+                tile_pos,
+                lonlat: xy_to_lonlat(&settings, tile_pos.x, tile_pos.y),
+                elevation: TileElevation(value),
+                temperature: TileTemperature(-value),
+                biome: biome_name.map(str::to_string),
+            })
+        );
+        assert_eq!(inspect(TilePos { x: 2, y: 0 }), None);
+    }
+}
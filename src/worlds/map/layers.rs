@@ -0,0 +1,192 @@
+//! Layers of the map in contiguous grids, one resource per layer:
+//! `LayerGrid<TileElevation>`, `LayerGrid<TileTemperature>` and
+//! `LayerGrid<TileBiome>`. They are filled in bulk from the generated world and
+//! read by index, tile entities only carry what the tilemap renders.
+//!
+//! Each grid tracks which of its regions changed since it was last drawn, so
+//! that edits only recolour the tiles they touch.
+
+use bevy::{math::URect, prelude::*};
+use bevy_ecs_tilemap::tiles::TilePos;
+
+/// Side of the square regions whose changes are tracked, in tiles.
+pub const REGION_SIZE: u32 = 32;
+
+/// Values of a layer stored row by row like `TilePos::to_index`.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct LayerGrid<T> {
+    width: u32,
+    height: u32,
+    values: Vec<T>,
+    /// Changed regions, row by row.
+    dirty: Vec<bool>,
+}
+
+impl<T> Default for LayerGrid<T> {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            values: Vec::new(),
+            dirty: Vec::new(),
+        }
+    }
+}
+
+impl<T> LayerGrid<T> {
+    /// Panics unless there are `width * height` values. The whole grid is
+    /// dirty.
+    pub fn new(width: u32, height: u32, values: Vec<T>) -> Self {
+        assert_eq!(values.len(), width as usize * height as usize);
+        let regions = width.div_ceil(REGION_SIZE) * height.div_ceil(REGION_SIZE);
+        Self {
+            width,
+            height,
+            values,
+            dirty: vec![true; regions as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Index of a tile in [`LayerGrid::values`], `None` out of the grid.
+    pub fn index(&self, tile_pos: &TilePos) -> Option<usize> {
+        (tile_pos.x < self.width && tile_pos.y < self.height)
+            .then(|| tile_pos.y as usize * self.width as usize + tile_pos.x as usize)
+    }
+
+    pub fn get(&self, tile_pos: &TilePos) -> Option<&T> {
+        self.index(tile_pos).map(|index| &self.values[index])
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Changes a tile and marks its region dirty, `false` out of the grid.
+    pub fn set(&mut self, tile_pos: &TilePos, value: T) -> bool {
+        let Some(index) = self.index(tile_pos) else {
+            return false;
+        };
+        self.values[index] = value;
+        let region = self.region(tile_pos);
+        self.dirty[region] = true;
+        true
+    }
+
+    /// Marks the regions overlapping a rectangle of tiles dirty, for edits
+    /// made through [`LayerGrid::values_mut`].
+    pub fn mark_dirty(&mut self, tiles: URect) {
+        let tiles = tiles.intersect(URect::new(0, 0, self.width, self.height));
+        if tiles.is_empty() {
+            return;
+        }
+        for y in tiles.min.y / REGION_SIZE..=(tiles.max.y - 1) / REGION_SIZE {
+            for x in tiles.min.x / REGION_SIZE..=(tiles.max.x - 1) / REGION_SIZE {
+                let region = self.region(&TilePos {
+                    x: x * REGION_SIZE,
+                    y: y * REGION_SIZE,
+                });
+                self.dirty[region] = true;
+            }
+        }
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.fill(true);
+    }
+
+    /// Values to edit in bulk, mark the edited tiles with
+    /// [`LayerGrid::mark_dirty`].
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.contains(&true)
+    }
+
+    /// Rectangles of tiles changed since [`LayerGrid::clear_dirty`], the max
+    /// corner excluded.
+    pub fn dirty_regions(&self) -> impl Iterator<Item = URect> + '_ {
+        let columns = self.width.div_ceil(REGION_SIZE);
+        self.dirty
+            .iter()
+            .enumerate()
+            .filter(|(_, &dirty)| dirty)
+            .map(move |(region, _)| {
+                let min =
+                    UVec2::new(region as u32 % columns, region as u32 / columns) * REGION_SIZE;
+                let max = (min + REGION_SIZE).min(UVec2::new(self.width, self.height));
+                URect::from_corners(min, max)
+            })
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty.fill(false);
+    }
+
+    fn region(&self, tile_pos: &TilePos) -> usize {
+        let columns = self.width.div_ceil(REGION_SIZE);
+        ((tile_pos.y / REGION_SIZE) * columns + tile_pos.x / REGION_SIZE) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::URect;
+    use bevy_ecs_tilemap::tiles::TilePos;
+    use rstest::rstest;
+
+    use super::{LayerGrid, REGION_SIZE};
+
+    #[rstest]
+    #[case(TilePos { x: 0, y: 0 }, Some(&0))]
+    #[case(TilePos { x: 2, y: 1 }, Some(&5))]
+    #[case(TilePos { x: 3, y: 0 }, None)]
+    #[case(TilePos { x: 0, y: 2 }, None)]
+    fn test_get(#[case] tile_pos: TilePos, #[case] expected: Option<&u32>) {
+        let grid = LayerGrid::new(3, 2, (0..6).collect());
+
+        assert_eq!(grid.get(&tile_pos), expected);
+    }
+
+    #[rstest]
+    fn test_dirty_regions() {
+        let mut grid = LayerGrid::new(REGION_SIZE + 8, 4, vec![0.; (REGION_SIZE as usize + 8) * 4]);
+        assert_eq!(grid.dirty_regions().count(), 2);
+
+        grid.clear_dirty();
+        assert!(!grid.is_dirty());
+        assert!(grid.set(
+            &TilePos {
+                x: REGION_SIZE + 1,
+                y: 2
+            },
+            1.
+        ));
+        assert!(!grid.set(&TilePos { x: 0, y: 4 }, 1.));
+
+        assert_eq!(
+            grid.dirty_regions().collect::<Vec<_>>(),
+            [URect::new(REGION_SIZE, 0, REGION_SIZE + 8, 4)]
+        );
+
+        grid.clear_dirty();
+        grid.mark_dirty(URect::new(2, 1, 3, 2));
+        assert_eq!(
+            grid.dirty_regions().collect::<Vec<_>>(),
+            [URect::new(0, 0, REGION_SIZE, 4)]
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! Level of detail: zoomed out, the map is drawn by summary levels whose
+//! cells aggregate square blocks of tiles, so that the number of tiles drawn
+//! does not grow with the size of the map.
+
+use std::collections::HashMap;
+
+use bevy::{
+    math::{Rect, URect},
+    prelude::*,
+};
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::worlds::{
+    export::biome_colors,
+    settings::{RenderMode, Settings},
+    utils::scale,
+};
+
+use super::{
+    biomes::Biomes,
+    drawing, fill_layer_grids, finish_rendering,
+    generator::{
+        biome::{BiomeId, TileBiome},
+        elevation::{ElevationGenerator, TileElevation},
+        temperature::TileTemperature,
+        MapGenerator,
+    },
+    layers::LayerGrid,
+    renderer, update_tiles_color, GeneratedWorld, MapSet, MapTilemap,
+};
+
+/// Tiles per side of the cells of the summary levels, from the finest. They
+/// divide the regions of the layer grids.
+pub const LOD_FACTORS: [u32; 2] = [4, 16];
+/// Width on screen below which tiles or cells are too small to be worth
+/// drawing, in pixels.
+const MIN_CELL_PIXELS: f32 = 8.;
+/// Brightness of the colour of the lowest cells, the highest being at full
+/// brightness.
+const MIN_CELL_SHADE: f64 = 0.5;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            select_lod.before(MapSet::Render),
+            update_summaries
+                .run_if(drawing)
+                .in_set(MapSet::Render)
+                .after(fill_layer_grids)
+                .after(update_tiles_color)
+                .before(finish_rendering),
+        ),
+    );
+}
+
+/// Tiles per side of the cells drawn: 1 for the tilemap itself, or one of
+/// [`LOD_FACTORS`].
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapLod(pub u32);
+
+impl Default for MapLod {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+/// Map drawn at a coarser level, with its cells and the sprites drawing them
+/// as its children.
+#[derive(Component)]
+pub(super) struct SummaryLevel {
+    factor: u32,
+    size: UVec2,
+    grid_size: Vec2,
+    cells: Vec<SummaryCell>,
+    sprites: Vec<Entity>,
+}
+
+/// Aggregate of a block of tiles: mean elevation and temperature, and the
+/// most common biome.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SummaryCell {
+    pub elevation: TileElevation,
+    pub temperature: TileTemperature,
+    pub biome: TileBiome,
+}
+
+/// Cells of a map of `map_size` tiles at a level, the last ones covering the
+/// remaining tiles.
+pub fn summary_size(map_size: UVec2, factor: u32) -> UVec2 {
+    UVec2::new(map_size.x.div_ceil(factor), map_size.y.div_ceil(factor))
+}
+
+/// Rectangle covered by the block of tiles of a cell, in the frame of the
+/// tilemap. The cells tile the map without overlapping: the rows of hexagons
+/// being shifted by half a tile every other row, a cell starts a quarter of a
+/// tile before its first column and halfway between its first row and the
+/// previous one.
+pub fn cell_rect(grid_size: Vec2, map_size: UVec2, factor: u32, cell: UVec2) -> Rect {
+    let min = cell * factor;
+    let max = ((cell + 1) * factor).min(map_size);
+    let corner = |tile: UVec2| {
+        Vec2::new(
+            (tile.x as f32 - 0.25) * grid_size.x,
+            (tile.y as f32 - 0.5) * 0.75 * grid_size.y,
+        )
+    };
+    Rect::from_corners(corner(min), corner(max))
+}
+
+/// Summarises the block of tiles of a cell, the first biome wins ties.
+pub fn summarise(
+    elevation: &LayerGrid<TileElevation>,
+    temperature: &LayerGrid<TileTemperature>,
+    biome: &LayerGrid<TileBiome>,
+    factor: u32,
+    cell: UVec2,
+) -> SummaryCell {
+    let tiles = URect::from_corners(cell * factor, (cell + 1) * factor).intersect(URect::new(
+        0,
+        0,
+        elevation.width(),
+        elevation.height(),
+    ));
+    let mut elevation_sum = 0.;
+    let mut temperature_sum = 0.;
+    let mut count = 0;
+    // Tiles of each biome, and the order they were first seen in
+    let mut biome_counts: HashMap<Option<BiomeId>, (usize, usize)> = HashMap::new();
+    for y in tiles.min.y..tiles.max.y {
+        for x in tiles.min.x..tiles.max.x {
+            let tile_pos = TilePos { x, y };
+            elevation_sum += elevation.get(&tile_pos).map_or(0., |tile| tile.0);
+            temperature_sum += temperature.get(&tile_pos).map_or(0., |tile| tile.0);
+            if let Some(tile_biome) = biome.get(&tile_pos) {
+                let seen = biome_counts.len();
+                biome_counts.entry(tile_biome.0).or_insert((0, seen)).0 += 1;
+            }
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return SummaryCell::default();
+    }
+
+    let biome = biome_counts
+        .into_iter()
+        .max_by_key(|(_, (tiles, seen))| (*tiles, std::cmp::Reverse(*seen)))
+        .and_then(|(tile_biome, _)| tile_biome);
+    SummaryCell {
+        elevation: TileElevation(elevation_sum / count as f64),
+        temperature: TileTemperature(temperature_sum / count as f64),
+        biome: TileBiome(biome),
+    }
+}
+
+/// Colour of a cell: the colour of its most common biome, shaded darker the
+/// lower its mean elevation, or the colour of a tile of its means when it has
+/// no biome.
+pub fn cell_color(settings: &Settings, biome_colors: &[[u8; 3]], cell: &SummaryCell) -> Color {
+    let Some([r, g, b]) = cell
+        .biome
+        .0
+        .and_then(|id| biome_colors.get(id as usize))
+        .copied()
+    else {
+        return renderer::tile_color(settings, &cell.elevation, &cell.temperature);
+    };
+    let [min, max] = ElevationGenerator::get_min_max(settings);
+    let shade =
+        scale(cell.elevation.0, min, max, MIN_CELL_SHADE, 1.).clamp(MIN_CELL_SHADE, 1.) as f32;
+    Color::srgb(
+        r as f32 / 255. * shade,
+        g as f32 / 255. * shade,
+        b as f32 / 255. * shade,
+    )
+}
+
+/// Picks the finest level whose cells are wide enough on screen with the zoom
+/// of the camera.
+fn select_lod(
+    mut lod: ResMut<MapLod>,
+    camera_query: Query<&OrthographicProjection, With<Camera2d>>,
+    tilemap_query: Query<&TilemapGridSize, With<MapTilemap>>,
+) {
+    let (Ok(projection), Ok(grid_size)) = (camera_query.get_single(), tilemap_query.get_single())
+    else {
+        return;
+    };
+    let tile_pixels = grid_size.x / projection.scale;
+    let factor = std::iter::once(1)
+        .chain(LOD_FACTORS)
+        .find(|factor| tile_pixels * *factor as f32 >= MIN_CELL_PIXELS)
+        .unwrap_or(LOD_FACTORS[LOD_FACTORS.len() - 1]);
+    lod.set_if_neq(MapLod(factor));
+}
+
+/// Spawns the summary levels for the size of the map, updates the cells of
+/// the dirty regions, every cell when the settings changed, and shows the
+/// level picked.
+fn update_summaries(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    lod: Res<MapLod>,
+    elevation: Res<LayerGrid<TileElevation>>,
+    temperature: Res<LayerGrid<TileTemperature>>,
+    biome: Res<LayerGrid<TileBiome>>,
+    generated_world: Res<GeneratedWorld>,
+    biomes: Res<Biomes>,
+    tilemap_query: Query<(&TilemapGridSize, &Transform), (With<MapTilemap>, Without<SummaryLevel>)>,
+    mut summary_query: Query<(Entity, &mut SummaryLevel, &mut Transform, &mut Visibility)>,
+    mut sprite_query: Query<&mut Sprite>,
+) {
+    let Ok((grid_size, tilemap_transform)) = tilemap_query.get_single() else {
+        return;
+    };
+    let grid_size = Vec2::new(grid_size.x, grid_size.y);
+    let map_size = UVec2::new(elevation.width(), elevation.height());
+    if elevation.is_empty()
+        || UVec2::new(temperature.width(), temperature.height()) != map_size
+        || UVec2::new(biome.width(), biome.height()) != map_size
+    {
+        return;
+    }
+    let drawn = (settings.elevation || settings.temperature)
+        && settings.render_mode == RenderMode::Tilemap
+        && !settings.streaming;
+    let visibility_of = |factor: u32| {
+        if drawn && lod.0 == factor {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        }
+    };
+    let biome_colors = biome_colors(&generated_world.0, &biomes.0);
+    let cell_color = |cell: &SummaryCell| cell_color(&settings, &biome_colors, cell);
+
+    let mut regions: Vec<URect> = elevation.dirty_regions().collect();
+    regions.extend(temperature.dirty_regions());
+    regions.extend(biome.dirty_regions());
+    let mut spawned = Vec::new();
+    for (entity, mut summary, mut transform, mut visibility) in summary_query.iter_mut() {
+        if summary.size != summary_size(map_size, summary.factor) || summary.grid_size != grid_size
+        {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        spawned.push(summary.factor);
+
+        let factor = summary.factor;
+        let mut touched = Vec::new();
+        let cells = if settings.is_changed() {
+            URect::from_corners(UVec2::ZERO, summary.size)
+        } else {
+            URect::EMPTY
+        };
+        for y in cells.min.y..cells.max.y {
+            for x in cells.min.x..cells.max.x {
+                touched.push(UVec2::new(x, y));
+            }
+        }
+        for region in &regions {
+            for y in region.min.y / factor..region.max.y.div_ceil(factor) {
+                for x in region.min.x / factor..region.max.x.div_ceil(factor) {
+                    let cell = UVec2::new(x, y);
+                    let index = (y * summary.size.x + x) as usize;
+                    summary.cells[index] =
+                        summarise(&elevation, &temperature, &biome, factor, cell);
+                    touched.push(cell);
+                }
+            }
+        }
+        for cell in touched {
+            let index = (cell.y * summary.size.x + cell.x) as usize;
+            let Some(sprite_entity) = summary.sprites.get(index) else {
+                continue;
+            };
+            if let Ok(mut sprite) = sprite_query.get_mut(*sprite_entity) {
+                sprite.color = cell_color(&summary.cells[index]);
+            }
+        }
+
+        if transform.translation != tilemap_transform.translation {
+            transform.translation = tilemap_transform.translation;
+        }
+        let shown = visibility_of(factor);
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+
+    for factor in LOD_FACTORS {
+        if spawned.contains(&factor) {
+            continue;
+        }
+        let size = summary_size(map_size, factor);
+        let cells: Vec<SummaryCell> = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| UVec2::new(x, y)))
+            .map(|cell| summarise(&elevation, &temperature, &biome, factor, cell))
+            .collect();
+
+        let sprites: Vec<Entity> = cells
+            .iter()
+            .enumerate()
+            .map(|(index, summary_cell)| {
+                let cell = UVec2::new(index as u32 % size.x, index as u32 / size.x);
+                let rect = cell_rect(grid_size, map_size, factor, cell);
+                commands
+                    .spawn(SpriteBundle {
+                        sprite: Sprite {
+                            color: cell_color(summary_cell),
+                            custom_size: Some(rect.size()),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(rect.center().extend(0.)),
+                        ..default()
+                    })
+                    .id()
+            })
+            .collect();
+
+        commands
+            .spawn((
+                SpatialBundle {
+                    transform: Transform::from_translation(tilemap_transform.translation),
+                    visibility: visibility_of(factor),
+                    ..default()
+                },
+                SummaryLevel {
+                    factor,
+                    size,
+                    grid_size,
+                    cells,
+                    sprites: sprites.clone(),
+                },
+            ))
+            .push_children(&sprites);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, state::app::StatesPlugin};
+    use bevy_ecs_tilemap::prelude::*;
+    use rstest::rstest;
+
+    use crate::worlds::{
+        map::{
+            biomes::Biomes,
+            generator::{biome::TileBiome, elevation::TileElevation, temperature::TileTemperature},
+            layers::LayerGrid,
+            pipeline, renderer, spawn_tilemap, MapState, MapTilemap,
+        },
+        settings::Settings,
+    };
+
+    use super::{
+        cell_color, cell_rect, plugin, summarise, summary_size, MapLod, SummaryCell, SummaryLevel,
+    };
+
+    #[rstest]
+    #[case(UVec2::new(0, 0), 0.5, Some(1))]
+    #[case(UVec2::new(1, 0), 2.5, Some(1))]
+    // The last cell only has the last column
+    #[case(UVec2::new(2, 0), 4., Some(2))]
+    fn test_summarise(#[case] cell: UVec2, #[case] mean: f64, #[case] mode: Option<u16>) {
+        // 5×2 tiles worth their column, the biome of the first four columns
+        // being 1 but for one tile
+        let values = |value: &dyn Fn(u32, u32) -> f64| -> Vec<f64> {
+            (0..2)
+                .flat_map(|y| (0..5).map(move |x| (x, y)))
+                .map(|(x, y)| value(x, y))
+                .collect()
+        };
+        let columns = values(&|x, _| x as f64);
+        let elevation = LayerGrid::new(5, 2, columns.iter().copied().map(TileElevation).collect());
+        let temperature = LayerGrid::new(
+            5,
+            2,
+            columns.iter().map(|x| TileTemperature(x * 2.)).collect(),
+        );
+        let biomes = values(&|x, y| match (x, y) {
+            (4, _) => 2.,
+            (0, 0) => 0.,
+            _ => 1.,
+        });
+        let biome = LayerGrid::new(
+            5,
+            2,
+            biomes.iter().map(|b| TileBiome(Some(*b as u16))).collect(),
+        );
+
+        // Cells of 2×2 tiles
+        assert_eq!(summary_size(UVec2::new(5, 2), 2), UVec2::new(3, 1));
+        let expected = SummaryCell {
+            elevation: TileElevation(mean),
+            temperature: TileTemperature(mean * 2.),
+            biome: TileBiome(mode),
+        };
+        assert_eq!(
+            summarise(&elevation, &temperature, &biome, 2, cell),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn test_cell_color_shades_biome() {
+        let settings = Settings::from_seed(0);
+        let colors = [[200, 100, 50]];
+        let cell = |elevation: f64, biome: Option<u16>| SummaryCell {
+            elevation: TileElevation(elevation),
+            temperature: TileTemperature(10.),
+            biome: TileBiome(biome),
+        };
+
+        // The highest cells have the colour of their biome, the lowest are
+        // half as bright
+        assert_eq!(
+            cell_color(&settings, &colors, &cell(20., Some(0))),
+            Color::srgb_u8(200, 100, 50)
+        );
+        assert_eq!(
+            cell_color(&settings, &colors, &cell(-20., Some(0))),
+            Color::srgb(100. / 255., 50. / 255., 25. / 255.)
+        );
+        // Without biome, the means are drawn like a tile
+        let without_biome = cell(5., None);
+        assert_eq!(
+            cell_color(&settings, &colors, &without_biome),
+            renderer::tile_color(
+                &settings,
+                &without_biome.elevation,
+                &without_biome.temperature
+            )
+        );
+    }
+
+    #[rstest]
+    fn test_lod_follows_zoom() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin, pipeline, plugin))
+            .init_resource::<Biomes>()
+            .insert_resource(Settings {
+                width: 20,
+                height: 10,
+                ..Settings::from_seed(0)
+            });
+        app.world_mut()
+            .run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+                spawn_tilemap(&mut commands, &settings, Handle::default());
+            });
+        let camera = app.world_mut().spawn(Camera2dBundle::default()).id();
+        for _ in 0..100 {
+            app.update();
+            if *app.world().resource::<State<MapState>>().get() == MapState::Ready {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        app.update();
+
+        let visible_levels = |app: &mut App| {
+            let world = app.world_mut();
+            let mut levels: Vec<u32> = world
+                .query::<(&SummaryLevel, &Visibility)>()
+                .iter(world)
+                .filter(|(_, visibility)| **visibility == Visibility::Visible)
+                .map(|(summary, _)| summary.factor)
+                .collect();
+            let tilemap_visibility = *world
+                .query_filtered::<&Visibility, With<MapTilemap>>()
+                .single(world);
+            if tilemap_visibility == Visibility::Visible {
+                levels.push(1);
+            }
+            levels
+        };
+        assert_eq!(*app.world().resource::<MapLod>(), MapLod(1));
+        assert_eq!(visible_levels(&mut app), vec![1]);
+
+        // Tiles of 50 pixels shrink to 2 pixels
+        for (scale, factor) in [(25., 4), (100., 16), (1., 1)] {
+            app.world_mut()
+                .entity_mut(camera)
+                .get_mut::<OrthographicProjection>()
+                .unwrap()
+                .scale = scale;
+            app.update();
+            assert_eq!(*app.world().resource::<MapLod>(), MapLod(factor));
+            assert_eq!(visible_levels(&mut app), vec![factor]);
+        }
+
+        // Every cell of a level summarises its tiles
+        let world = app.world_mut();
+        let summary = world
+            .query::<&SummaryLevel>()
+            .iter(world)
+            .find(|summary| summary.factor == 4)
+            .unwrap();
+        assert_eq!(summary.size, UVec2::new(5, 3));
+        let expected = summarise(
+            world.resource::<LayerGrid<TileElevation>>(),
+            world.resource::<LayerGrid<TileTemperature>>(),
+            world.resource::<LayerGrid<TileBiome>>(),
+            4,
+            UVec2::new(4, 2),
+        );
+        assert_eq!(summary.cells[14], expected);
+
+        // The last cell covers the 4×2 tiles of its block, centred on them
+        let (summary_translation, sprite_entity) = world
+            .query::<(&SummaryLevel, &Transform)>()
+            .iter(world)
+            .find(|(summary, _)| summary.factor == 4)
+            .map(|(summary, transform)| (transform.translation, summary.sprites[14]))
+            .unwrap();
+        let (grid_size, tilemap_transform) = world
+            .query_filtered::<(&TilemapGridSize, &Transform), With<MapTilemap>>()
+            .single(world);
+        let grid_size = Vec2::new(grid_size.x, grid_size.y);
+        assert_eq!(summary_translation, tilemap_transform.translation);
+        // Even rows are shifted half a tile to the right
+        let tile_centre = |x: u32, y: u32| {
+            let shift = if y.is_multiple_of(2) { 0.5 } else { 0. };
+            Vec2::new(
+                (x as f32 + shift) * grid_size.x,
+                y as f32 * 0.75 * grid_size.y,
+            )
+        };
+        let block: Vec<Vec2> = (8..10)
+            .flat_map(|y| (16..20).map(move |x| tile_centre(x, y)))
+            .collect();
+        let block_centre = block.iter().sum::<Vec2>() / block.len() as f32;
+        let rect = Rect::from_center_size(
+            world
+                .get::<Transform>(sprite_entity)
+                .unwrap()
+                .translation
+                .truncate(),
+            world
+                .get::<Sprite>(sprite_entity)
+                .unwrap()
+                .custom_size
+                .unwrap(),
+        );
+        assert!((rect.center() - block_centre).length() < 1e-3);
+        assert!(block.iter().all(|centre| rect.contains(*centre)));
+        assert_eq!(
+            rect.size(),
+            Vec2::new(4. * grid_size.x, 2. * 0.75 * grid_size.y)
+        );
+        assert_eq!(
+            rect,
+            cell_rect(grid_size, UVec2::new(20, 10), 4, UVec2::new(4, 2))
+        );
+    }
+}
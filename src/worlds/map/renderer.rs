@@ -1,2 +1,31 @@
+use bevy::prelude::*;
+
+use crate::worlds::map::generator::{elevation::TileElevation, temperature::TileTemperature};
+use crate::worlds::settings::Settings;
+
+use elevation::ElevationMapRenderer;
+use temperature::TemperatureMapRenderer;
+
 pub mod elevation;
 pub mod temperature;
+
+/// Colour of a tile on the map, mixing the layers enabled in the settings.
+pub fn tile_color(
+    settings: &Settings,
+    tile_elevation: &TileElevation,
+    tile_temperature: &TileTemperature,
+) -> Color {
+    let mut color = Color::srgba(1., 1., 1., 1.);
+
+    if settings.elevation {
+        color.mix_assign(ElevationMapRenderer.get_color(tile_elevation), 1.);
+    }
+    if settings.temperature {
+        color.mix_assign(
+            TemperatureMapRenderer.get_color(tile_temperature, settings),
+            settings.temperature_factor,
+        );
+    }
+
+    color
+}
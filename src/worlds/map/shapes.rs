@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use rand::Rng;
 
+use crate::worlds::seed::{rng, SeedStream};
 use crate::worlds::utils::scale;
 
 use super::Settings;
@@ -47,7 +48,7 @@ impl ShapeGenerator for CircleCenteredShape {
 pub struct ContinentsShape {
     count: usize,
     random_points: Vec<(f64, f64)>,
-    seed: u32,
+    seed: u64,
 }
 
 impl Default for ContinentsShape {
@@ -64,10 +65,10 @@ impl ShapeGenerator for ContinentsShape {
     fn init(&mut self, config: &Settings) {
         let x_max = config.width as f64;
         let y_max = config.height as f64;
-        let mut rng = rand::thread_rng();
+        let mut rng = rng(config.world_seed, SeedStream::Shapes);
 
         self.count = 1;
-        self.seed = config.elevation_gen.seed;
+        self.seed = config.world_seed;
         self.random_points.clear();
 
         for i in 0..self.count {
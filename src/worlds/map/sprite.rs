@@ -0,0 +1,358 @@
+//! Image renderer: the tiles rasterised on the CPU into a single image, shown
+//! as a sprite instead of the tilemap. The image is drawn like the PNG exports,
+//! with [`rasterise_tiles`], and only the dirty regions of the layers are
+//! redrawn. The tilemap and its wrap copy are kept for their layout, without
+//! tile entities.
+
+use bevy::{
+    math::URect,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+use bevy_ecs_tilemap::prelude::*;
+use image::{ImageBuffer, Rgba};
+
+use crate::worlds::export::{map_pixel, rasterise_tiles, Rasterisation};
+use crate::worlds::settings::{RenderMode, Settings};
+
+use super::{
+    dirty_regions, drawing, fill_layer_grids, finish_rendering,
+    generator::{elevation::TileElevation, temperature::TileTemperature},
+    has_tile_entities,
+    layers::LayerGrid,
+    resize_map, spawn_tiles,
+    wrap::{toggle_wrap_copy, WrapCopy},
+    MapSet, MapTilemap,
+};
+
+/// Width of the image beyond which hexagons shrink, in pixels.
+const MAX_IMAGE_WIDTH: u32 = 4096;
+/// Width of the hexagons of small maps, in pixels.
+const MAX_TILE_WIDTH: u32 = 16;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            update_map_image
+                .run_if(|settings: Res<Settings>| settings.render_mode == RenderMode::Image)
+                .run_if(drawing)
+                .in_set(MapSet::Render)
+                .after(fill_layer_grids)
+                .before(finish_rendering),
+            remove_map_image.run_if(|settings: Res<Settings>| {
+                settings.is_changed() && settings.render_mode == RenderMode::Tilemap
+            }),
+            toggle_tiles
+                .in_set(MapSet::Prepare)
+                .after(resize_map)
+                .after(toggle_wrap_copy)
+                .run_if(resource_changed::<Settings>),
+        ),
+    );
+}
+
+#[derive(Component)]
+pub(super) struct MapSprite;
+
+/// Hexagons as wide as the image allows for a map of `map_size` tiles.
+pub fn image_rasterisation(map_size: UVec2) -> Rasterisation {
+    Rasterisation::Hexagons {
+        tile_width: (MAX_IMAGE_WIDTH / (map_size.x + 1)).clamp(2, MAX_TILE_WIDTH),
+    }
+}
+
+/// Redraws the dirty regions of the image, all of it when the settings or the
+/// size of the map changed, and spawns the sprite on the first drawing.
+fn update_map_image(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    elevation: Res<LayerGrid<TileElevation>>,
+    temperature: Res<LayerGrid<TileTemperature>>,
+    mut images: ResMut<Assets<Image>>,
+    tilemap_query: Query<(&Transform, &TilemapGridSize), (With<MapTilemap>, Without<MapSprite>)>,
+    mut sprite_query: Query<
+        (&Handle<Image>, &mut Sprite, &mut Transform, &mut Visibility),
+        With<MapSprite>,
+    >,
+) {
+    let Ok((tilemap_transform, grid_size)) = tilemap_query.get_single() else {
+        return;
+    };
+    let map_size = UVec2::new(elevation.width(), elevation.height());
+    if elevation.is_empty() || UVec2::new(temperature.width(), temperature.height()) != map_size {
+        return;
+    }
+    let rasterisation = image_rasterisation(map_size);
+    let (width, height) = rasterisation.dimensions(map_size);
+
+    // The image spans the tiles, centred on the middle of their centres
+    let custom_size = Vec2::new(
+        (map_size.x as f32 + 0.5) * grid_size.x,
+        (0.75 * (map_size.y as f32 - 1.) + 1.) * grid_size.y,
+    );
+    let centre = Vec3::new(
+        (map_size.x as f32 - 0.5) * grid_size.x / 2.,
+        0.75 * (map_size.y as f32 - 1.) * grid_size.y / 2.,
+        0.,
+    );
+    let transform = Transform::from_translation(tilemap_transform.translation + centre);
+    let visibility = if (settings.elevation || settings.temperature) && !settings.streaming {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+
+    let everything = vec![URect::new(0, 0, map_size.x, map_size.y)];
+    let pixel = |index: usize| {
+        let tile_pos = TilePos {
+            x: index as u32 % map_size.x,
+            y: index as u32 / map_size.x,
+        };
+        map_pixel(
+            &settings,
+            &tile_pos,
+            &elevation.values()[index],
+            &temperature.values()[index],
+        )
+    };
+    let draw = |image: &mut Image, regions: &[URect]| {
+        let Some(mut buffer) =
+            ImageBuffer::<Rgba<u8>, &mut [u8]>::from_raw(width, height, &mut image.data[..])
+        else {
+            return;
+        };
+        for &tiles in regions {
+            rasterise_tiles(
+                &mut buffer,
+                map_size,
+                rasterisation,
+                tiles,
+                Rgba([0, 0, 0, 0]),
+                &pixel,
+            );
+        }
+    };
+
+    let Ok((handle, mut sprite, mut sprite_transform, mut sprite_visibility)) =
+        sprite_query.get_single_mut()
+    else {
+        let mut image = blank_image(width, height);
+        draw(&mut image, &everything);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(custom_size),
+                    ..default()
+                },
+                texture: images.add(image),
+                transform,
+                visibility,
+                ..default()
+            },
+            MapSprite,
+        ));
+        return;
+    };
+
+    let Some(image) = images.get(handle) else {
+        return;
+    };
+    let resized = image.width() != width || image.height() != height;
+    let regions = if resized || settings.is_changed() {
+        everything
+    } else {
+        dirty_regions(&settings, &elevation, &temperature)
+    };
+    // Borrowing the image mutably uploads it to the GPU again
+    if !regions.is_empty() {
+        let Some(image) = images.get_mut(handle) else {
+            return;
+        };
+        if resized {
+            *image = blank_image(width, height);
+        }
+        draw(image, &regions);
+    }
+
+    if settings.is_changed() || sprite.custom_size != Some(custom_size) {
+        sprite.custom_size = Some(custom_size);
+        *sprite_transform = transform;
+        *sprite_visibility = visibility;
+    }
+}
+
+fn blank_image(width: u32, height: u32) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        // Kept on the CPU to redraw regions of it
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+/// Despawns the tile entities of the tilemap and its wrap copy with the image
+/// renderer or streamed chunks, and spawns them back otherwise.
+fn toggle_tiles(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut tilemap_query: Query<
+        (Entity, &TilemapSize, &mut TileStorage),
+        Or<(With<MapTilemap>, With<WrapCopy>)>,
+    >,
+) {
+    for (entity, size, mut tile_storage) in tilemap_query.iter_mut() {
+        let has_tiles = tile_storage.iter().any(Option::is_some);
+        match (has_tile_entities(&settings), has_tiles) {
+            (false, true) => {
+                for tile_entity in tile_storage.drain() {
+                    commands.entity(tile_entity).despawn();
+                }
+            }
+            (true, false) => {
+                *tile_storage = spawn_tiles(&mut commands, &settings, entity, *size);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn remove_map_image(mut commands: Commands, sprite_query: Query<Entity, With<MapSprite>>) {
+    for entity in sprite_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, state::app::StatesPlugin};
+    use bevy_ecs_tilemap::prelude::*;
+    use rstest::rstest;
+
+    use crate::worlds::{
+        export::{layer_image, Layer},
+        map::{biomes::Biomes, pipeline, spawn_tilemap, GeneratedWorld, MapState},
+        settings::{RenderMode, Settings},
+    };
+
+    use super::{image_rasterisation, plugin, MapSprite};
+
+    #[rstest]
+    fn test_image_matches_export() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            StatesPlugin,
+            AssetPlugin::default(),
+            pipeline,
+            plugin,
+        ))
+        .init_asset::<Image>()
+        .init_resource::<Biomes>()
+        .insert_resource(Settings {
+            width: 6,
+            height: 4,
+            render_mode: RenderMode::Image,
+            ..Settings::from_seed(0)
+        });
+        app.world_mut()
+            .run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+                spawn_tilemap(&mut commands, &settings, Handle::default());
+            });
+        for _ in 0..100 {
+            app.update();
+            if *app.world().resource::<State<MapState>>().get() == MapState::Ready {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        app.update();
+
+        let world = app.world_mut();
+        let handle = world
+            .query_filtered::<&Handle<Image>, With<MapSprite>>()
+            .single(world)
+            .clone();
+        let settings = world.resource::<Settings>().clone();
+        let generated = &world.resource::<GeneratedWorld>().0;
+        let expected = layer_image(
+            generated,
+            &settings,
+            Layer::Map,
+            &[],
+            image_rasterisation(UVec2::new(6, 4)),
+        );
+        let image = world.resource::<Assets<Image>>().get(&handle).unwrap();
+
+        assert_eq!((image.width(), image.height()), expected.dimensions());
+        assert_eq!(image.data, expected.into_raw());
+    }
+
+    #[rstest]
+    fn test_image_mode_has_no_tiles() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            StatesPlugin,
+            AssetPlugin::default(),
+            pipeline,
+            plugin,
+        ))
+        .init_asset::<Image>()
+        .init_resource::<Biomes>()
+        .insert_resource(Settings {
+            width: 6,
+            height: 4,
+            render_mode: RenderMode::Image,
+            ..Settings::from_seed(0)
+        });
+        app.world_mut()
+            .run_system_once(|mut commands: Commands, settings: Res<Settings>| {
+                spawn_tilemap(&mut commands, &settings, Handle::default());
+            });
+        for _ in 0..100 {
+            app.update();
+            if *app.world().resource::<State<MapState>>().get() == MapState::Ready {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let tile_count = |app: &mut App| {
+            let world = app.world_mut();
+            world.query::<&TilePos>().iter(world).count()
+        };
+        assert_eq!(tile_count(&mut app), 0);
+
+        // Without edits, the image is not uploaded again
+        let mut reader = app
+            .world()
+            .resource::<Events<AssetEvent<Image>>>()
+            .get_reader();
+        let mut modified = 0;
+        for _ in 0..3 {
+            app.update();
+            let events = app.world().resource::<Events<AssetEvent<Image>>>();
+            modified += reader
+                .read(events)
+                .filter(|event| matches!(event, AssetEvent::Modified { .. }))
+                .count();
+        }
+        assert_eq!(modified, 0);
+
+        app.world_mut().resource_mut::<Settings>().render_mode = RenderMode::Tilemap;
+        app.update();
+        assert_eq!(tile_count(&mut app), 24);
+    }
+}
@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::worlds::settings::Settings;
+
+use super::{resize_map, spawn_tiles, MapSet, MapTilemap};
+
+/// Copy of the tilemap shown next to it when the world wraps, so that the
+/// camera sees the other side of the seam.
+///
+/// It gets the tile colours like the tilemap, panning past the middle of
+/// the copy moves the camera back by the width of the map.
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            toggle_wrap_copy
+                .in_set(MapSet::Prepare)
+                .after(resize_map)
+                .run_if(resource_changed::<Settings>),
+            follow_camera.run_if(|settings: Res<Settings>| settings.wrap),
+        ),
+    );
+}
+
+#[derive(Component)]
+pub(super) struct WrapCopy;
+
+type MainTilemap<'a> = (
+    &'a TilemapSize,
+    &'a TilemapGridSize,
+    &'a TilemapTileSize,
+    &'a TilemapType,
+    &'a TilemapTexture,
+    &'a Transform,
+);
+
+pub(super) fn toggle_wrap_copy(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    tilemap_query: Query<MainTilemap, (With<MapTilemap>, Without<WrapCopy>)>,
+    copy_query: Query<(Entity, &TileStorage), With<WrapCopy>>,
+) {
+    let copy = copy_query.get_single().ok();
+    match (settings.wrap, copy) {
+        (true, None) => {
+            let Ok((size, grid_size, tile_size, map_type, texture, transform)) =
+                tilemap_query.get_single()
+            else {
+                return;
+            };
+
+            let copy_entity = commands.spawn_empty().id();
+            let storage = spawn_tiles(&mut commands, &settings, copy_entity, *size);
+
+            commands.entity(copy_entity).insert((
+                TilemapBundle {
+                    grid_size: *grid_size,
+                    map_type: *map_type,
+                    tile_size: *tile_size,
+                    size: *size,
+                    storage,
+                    texture: texture.clone(),
+                    transform: *transform,
+                    ..default()
+                },
+                WrapCopy,
+            ));
+        }
+        (false, Some((copy_entity, storage))) => {
+            for tile_entity in storage.iter().flatten() {
+                commands.entity(*tile_entity).despawn();
+            }
+            commands.entity(copy_entity).despawn();
+        }
+        _ => {}
+    }
+}
+
+/// Keeps the copy on the side the camera looks at and the camera within a
+/// period of the map.
+fn follow_camera(
+    mut camera_query: Query<
+        &mut Transform,
+        (With<Camera2d>, Without<TilemapSize>, Without<WrapCopy>),
+    >,
+    tilemap_query: Query<
+        (&Transform, &TilemapSize, &TilemapGridSize, &Visibility),
+        (With<MapTilemap>, Without<WrapCopy>),
+    >,
+    mut copy_query: Query<(&mut Transform, &mut Visibility), With<WrapCopy>>,
+) {
+    let Ok((tilemap_transform, size, grid_size, visibility)) = tilemap_query.get_single() else {
+        return;
+    };
+    let Ok((mut copy_transform, mut copy_visibility)) = copy_query.get_single_mut() else {
+        return;
+    };
+    let period = size.x as f32 * grid_size.x;
+    let centre = tilemap_transform.translation.x + period / 2.;
+
+    for mut camera_transform in camera_query.iter_mut() {
+        let offset = camera_transform.translation.x - centre;
+        if offset.abs() > period / 2. {
+            camera_transform.translation.x -= period * offset.signum();
+        }
+        let side = (camera_transform.translation.x - centre).signum();
+        copy_transform.translation = tilemap_transform.translation + Vec3::X * period * side;
+    }
+    *copy_visibility = *visibility;
+}
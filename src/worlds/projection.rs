@@ -0,0 +1,308 @@
+//! Map projections between longitude and latitude, in degrees, and positions
+//! on the map normalized to `[-1, 1]`, west to east and south to north. Each
+//! projection is stretched to fill the map.
+
+use std::{f64::consts::PI, fmt, str::FromStr};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Web Mercator stops at the latitude that makes the map square.
+pub const MERCATOR_MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// Robinson table, every 5° of latitude: length of the parallel and distance
+/// to the equator, both relative to the equator and the poles.
+const ROBINSON: [(f64, f64); 19] = [
+    (1.0000, 0.0000),
+    (0.9986, 0.0620),
+    (0.9954, 0.1240),
+    (0.9900, 0.1860),
+    (0.9822, 0.2480),
+    (0.9730, 0.3100),
+    (0.9600, 0.3720),
+    (0.9427, 0.4340),
+    (0.9216, 0.4958),
+    (0.8962, 0.5571),
+    (0.8679, 0.6176),
+    (0.8350, 0.6769),
+    (0.7986, 0.7346),
+    (0.7597, 0.7903),
+    (0.7186, 0.8435),
+    (0.6732, 0.8936),
+    (0.6213, 0.9394),
+    (0.5722, 0.9761),
+    (0.5322, 1.0000),
+];
+
+/// Orthographic weights grow without bound towards the limb.
+const MAX_ORTHOGRAPHIC_WEIGHT: f64 = 100.;
+
+#[derive(Reflect, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Projection {
+    /// Longitude and latitude as is, the poles are stretched to the whole width.
+    #[default]
+    Equirectangular,
+    /// Conformal, up to [`MERCATOR_MAX_LATITUDE`].
+    Mercator,
+    /// Equal-area ellipse.
+    Mollweide,
+    /// Compromise between shapes and areas.
+    Robinson,
+    /// The hemisphere around longitude and latitude 0, as seen from space.
+    Orthographic,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 5] = [
+        Projection::Equirectangular,
+        Projection::Mercator,
+        Projection::Mollweide,
+        Projection::Robinson,
+        Projection::Orthographic,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Projection::Equirectangular => "equirectangular",
+            Projection::Mercator => "mercator",
+            Projection::Mollweide => "mollweide",
+            Projection::Robinson => "robinson",
+            Projection::Orthographic => "orthographic",
+        }
+    }
+
+    /// Position of a longitude and latitude on the map, `None` when the
+    /// projection does not show it.
+    pub fn forward(&self, lon: f64, lat: f64) -> Option<(f64, f64)> {
+        let (lambda, phi) = (lon.to_radians(), lat.to_radians());
+        match self {
+            Projection::Equirectangular => Some((lon / 180., lat / 90.)),
+            Projection::Mercator => (lat.abs() <= MERCATOR_MAX_LATITUDE)
+                .then(|| (lambda / PI, (PI / 4. + phi / 2.).tan().ln() / PI)),
+            Projection::Mollweide => {
+                let theta = mollweide_theta(phi);
+                Some((lambda * theta.cos() / PI, theta.sin()))
+            }
+            Projection::Robinson => {
+                let (x, y) = robinson(lat.abs());
+                Some((x * lambda / PI, y * lat.signum()))
+            }
+            Projection::Orthographic => {
+                (phi.cos() * lambda.cos() >= 0.).then(|| (phi.cos() * lambda.sin(), phi.sin()))
+            }
+        }
+    }
+
+    /// Longitude and latitude at a position of the map, `None` out of the
+    /// projected globe.
+    pub fn inverse(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let inside = match self {
+            Projection::Equirectangular | Projection::Mercator => x.abs() <= 1. && y.abs() <= 1.,
+            Projection::Mollweide | Projection::Orthographic => x * x + y * y <= 1. + 1e-12,
+            Projection::Robinson => {
+                y.abs() <= 1. && x.abs() <= robinson(robinson_latitude(y.abs())).0 + 1e-12
+            }
+        };
+        inside.then(|| self.inverse_clamped(x, y))
+    }
+
+    /// Same as [`Projection::inverse`], positions out of the globe take the
+    /// longitude and latitude of the nearest edge of the globe.
+    pub fn inverse_clamped(&self, x: f64, y: f64) -> (f64, f64) {
+        let (x, y) = (x.clamp(-1., 1.), y.clamp(-1., 1.));
+        match self {
+            Projection::Equirectangular => (x * 180., y * 90.),
+            Projection::Mercator => {
+                let phi = 2. * (y * PI).exp().atan() - PI / 2.;
+                (x * 180., phi.to_degrees())
+            }
+            Projection::Mollweide => {
+                let theta = y.asin();
+                let phi = ((2. * theta + (2. * theta).sin()) / PI)
+                    .clamp(-1., 1.)
+                    .asin();
+                let lon = if theta.cos() > 0. {
+                    (x / theta.cos() * 180.).clamp(-180., 180.)
+                } else {
+                    0.
+                };
+                (lon, phi.to_degrees())
+            }
+            Projection::Robinson => {
+                let lat = robinson_latitude(y.abs());
+                let lon = (x / robinson(lat).0 * 180.).clamp(-180., 180.);
+                (lon, lat * y.signum())
+            }
+            Projection::Orthographic => {
+                let rho = (x * x + y * y).sqrt();
+                let (x, y, depth) = if rho >= 1. {
+                    (x / rho, y / rho, 0.)
+                } else {
+                    (x, y, (1. - rho * rho).sqrt())
+                };
+                (x.atan2(depth).to_degrees(), y.asin().to_degrees())
+            }
+        }
+    }
+
+    /// Area of the globe, in steradians, per unit of map area around a
+    /// position, 0 out of the globe. Map cells of equal size weigh their share
+    /// of the globe with it.
+    pub fn area_weight(&self, x: f64, y: f64) -> f64 {
+        let Some((_, lat)) = self.inverse(x, y) else {
+            return 0.;
+        };
+        let cos = lat.to_radians().cos();
+        match self {
+            Projection::Equirectangular => cos * PI * PI / 2.,
+            Projection::Mercator => cos * cos * PI * PI,
+            // The whole sphere on the unit disc
+            Projection::Mollweide => 4.,
+            Projection::Robinson => {
+                let index = ((lat.abs() / 5.) as usize).min(ROBINSON.len() - 2);
+                let slope = (ROBINSON[index + 1].1 - ROBINSON[index].1) / 5f64.to_radians();
+                cos * PI / (robinson(lat.abs()).0 * slope)
+            }
+            Projection::Orthographic => {
+                let depth = (1. - x * x - y * y).max(0.).sqrt();
+                (1. / depth).min(MAX_ORTHOGRAPHIC_WEIGHT)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Projection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Projection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Projection::ALL
+            .into_iter()
+            .find(|projection| projection.name() == s.to_lowercase())
+            .ok_or_else(|| {
+                let names: Vec<_> = Projection::ALL.iter().map(Projection::name).collect();
+                format!(
+                    "unknown projection '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Auxiliary angle of the Mollweide projection, solving
+/// `2θ + sin 2θ = π sin φ` with Newton's method.
+fn mollweide_theta(phi: f64) -> f64 {
+    if (phi.abs() - PI / 2.).abs() < 1e-12 {
+        return phi;
+    }
+    let target = PI * phi.sin();
+    let mut theta = phi;
+    for _ in 0..50 {
+        let delta = (2. * theta + (2. * theta).sin() - target) / (2. + 2. * (2. * theta).cos());
+        theta -= delta;
+        if delta.abs() < 1e-12 {
+            break;
+        }
+    }
+    theta
+}
+
+/// Interpolated Robinson table at a latitude between 0 and 90.
+fn robinson(lat: f64) -> (f64, f64) {
+    let index = ((lat / 5.) as usize).min(ROBINSON.len() - 2);
+    let t = lat / 5. - index as f64;
+    let ((x0, y0), (x1, y1)) = (ROBINSON[index], ROBINSON[index + 1]);
+    (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t)
+}
+
+/// Latitude between 0 and 90 of a distance to the equator in the Robinson table.
+fn robinson_latitude(y: f64) -> f64 {
+    let index = ROBINSON
+        .windows(2)
+        .position(|pair| y <= pair[1].1)
+        .unwrap_or(ROBINSON.len() - 2);
+    let (y0, y1) = (ROBINSON[index].1, ROBINSON[index + 1].1);
+    (index as f64 + ((y - y0) / (y1 - y0)).clamp(0., 1.)) * 5.
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use rstest::rstest;
+
+    use super::{Projection, MERCATOR_MAX_LATITUDE};
+
+    #[rstest]
+    fn test_round_trip() {
+        for projection in Projection::ALL {
+            for (lon, lat) in [(0., 0.), (-150., 30.), (45., -60.), (179., 80.)] {
+                let Some((x, y)) = projection.forward(lon, lat) else {
+                    assert_eq!(projection, Projection::Orthographic);
+                    continue;
+                };
+                assert!(x.abs() <= 1. && y.abs() <= 1.);
+                let (round_lon, round_lat) = projection.inverse(x, y).unwrap();
+                assert!(
+                    (round_lon - lon).abs() < 1e-6 && (round_lat - lat).abs() < 1e-6,
+                    "{} of ({}, {}) is ({}, {})",
+                    projection,
+                    lon,
+                    lat,
+                    round_lon,
+                    round_lat
+                );
+            }
+        }
+    }
+
+    #[rstest]
+    #[case::mollweide(Projection::Mollweide)]
+    #[case::robinson(Projection::Robinson)]
+    fn test_out_of_globe_keeps_latitude(#[case] projection: Projection) {
+        let (_, lat) = projection.inverse(0., 0.9).unwrap();
+
+        assert_eq!(projection.inverse(-1., 0.9), None);
+        assert_eq!(projection.inverse_clamped(-1., 0.9), (-180., lat));
+    }
+
+    #[rstest]
+    fn test_orthographic_limb() {
+        let (lon, lat) = Projection::Orthographic.inverse_clamped(1., 1.);
+
+        assert_eq!(Projection::Orthographic.inverse(1., 1.), None);
+        assert!((lon - 90.).abs() < 1e-9 && (lat - 45.).abs() < 1e-9);
+    }
+
+    #[rstest]
+    #[case::equirectangular(Projection::Equirectangular, 4. * PI)]
+    #[case::mercator(Projection::Mercator, 4. * PI * MERCATOR_MAX_LATITUDE.to_radians().sin())]
+    #[case::mollweide(Projection::Mollweide, 4. * PI)]
+    #[case::robinson(Projection::Robinson, 4. * PI)]
+    #[case::orthographic(Projection::Orthographic, 2. * PI)]
+    fn test_area_weights_cover_the_globe(#[case] projection: Projection, #[case] area: f64) {
+        let steps = 800;
+        let cell = 2. / steps as f64;
+        let total: f64 = (0..steps * steps)
+            .map(|i| {
+                let x = -1. + cell * ((i % steps) as f64 + 0.5);
+                let y = -1. + cell * ((i / steps) as f64 + 0.5);
+                projection.area_weight(x, y) * cell * cell
+            })
+            .sum();
+
+        assert!(
+            (total - area).abs() / area < 0.02,
+            "{} covers {}",
+            projection,
+            total
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use super::map::generator::world::World;
+use super::settings::{check_settings, InvalidSetting, Settings};
+
+/// Save file written and loaded from the "World" window.
+pub const DEFAULT_SAVE_PATH: &str = "world.sav";
+
+/// First bytes of every save file.
+const MAGIC: &[u8; 8] = b"WORLDSAV";
+
+/// Bump it whenever the layout changes, and migrate or reject the older ones
+/// in [`read_world`].
+const SAVE_VERSION: u16 = 1;
+
+/// Raw value of tiles without biome.
+const NO_BIOME: u16 = u16::MAX;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    NotASave,
+    UnsupportedVersion(u16),
+    Corrupted(&'static str),
+    Settings(toml::de::Error),
+    InvalidSettings(Vec<InvalidSetting>),
+    /// The world is not the size of its settings, width and height of both.
+    SizeMismatch {
+        world: (u32, u32),
+        settings: (u32, u32),
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "File error: {}", e),
+            SaveError::NotASave => write!(f, "Not a world save file"),
+            SaveError::UnsupportedVersion(v) if *v > SAVE_VERSION => write!(
+                f,
+                "Save format version {} is newer than the supported version {}, update worlds",
+                v, SAVE_VERSION
+            ),
+            SaveError::UnsupportedVersion(v) => write!(
+                f,
+                "Save format version {} is no longer supported (current version is {}), \
+                 regenerate the world from its settings",
+                v, SAVE_VERSION
+            ),
+            SaveError::Corrupted(what) => write!(f, "Corrupted save file: {}", what),
+            SaveError::Settings(e) => write!(f, "Invalid settings in save file: {}", e),
+            SaveError::InvalidSettings(errors) => {
+                let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+                write!(f, "Invalid settings in save file: {}", errors.join(", "))
+            }
+            SaveError::SizeMismatch { world, settings } => write!(
+                f,
+                "World of {}x{} tiles saved with settings for {}x{} tiles",
+                world.0, world.1, settings.0, settings.1
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => SaveError::Corrupted("truncated"),
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => {
+                SaveError::Corrupted("invalid compressed data")
+            }
+            _ => SaveError::Io(e),
+        }
+    }
+}
+
+/// Checked before creating the file, so that a failed save leaves it as it was.
+pub fn save_world(world: &World, settings: &Settings, path: &Path) -> Result<(), SaveError> {
+    check_size(world, settings)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_world(world, settings, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn load_world(path: &Path) -> Result<(Settings, World), SaveError> {
+    read_world(BufReader::new(File::open(path)?))
+}
+
+/// Writes the magic and version, then deflates the settings TOML, the
+/// dimensions, the biome names and every layer, little-endian and row by row.
+/// Worlds that are not the size of their settings are rejected, like
+/// [`read_world`] does.
+pub fn write_world(
+    world: &World,
+    settings: &Settings,
+    writer: impl Write,
+) -> Result<(), SaveError> {
+    check_size(world, settings)?;
+    encode_world(world, settings, writer)
+}
+
+fn check_size(world: &World, settings: &Settings) -> Result<(), SaveError> {
+    if (world.width, world.height) != (settings.width, settings.height) {
+        return Err(SaveError::SizeMismatch {
+            world: (world.width, world.height),
+            settings: (settings.width, settings.height),
+        });
+    }
+    Ok(())
+}
+
+fn encode_world(
+    world: &World,
+    settings: &Settings,
+    mut writer: impl Write,
+) -> Result<(), SaveError> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&SAVE_VERSION.to_le_bytes())?;
+
+    let mut encoder = DeflateEncoder::new(writer, Compression::default());
+    let settings = toml::to_string(settings).expect("Settings should serialize to TOML");
+    write_bytes(&mut encoder, settings.as_bytes())?;
+    encoder.write_all(&world.width.to_le_bytes())?;
+    encoder.write_all(&world.height.to_le_bytes())?;
+
+    encoder.write_all(&(world.biome_names.len() as u32).to_le_bytes())?;
+    for name in &world.biome_names {
+        write_bytes(&mut encoder, name.as_bytes())?;
+    }
+    for value in world.elevation.iter().chain(&world.temperature) {
+        encoder.write_all(&value.to_le_bytes())?;
+    }
+    for id in &world.biome {
+        encoder.write_all(&id.unwrap_or(NO_BIOME).to_le_bytes())?;
+    }
+
+    encoder.finish()?;
+    Ok(())
+}
+
+pub fn read_world(mut reader: impl Read) -> Result<(Settings, World), SaveError> {
+    let mut magic = [0; MAGIC.len()];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|_| SaveError::NotASave)?;
+    if &magic != MAGIC {
+        return Err(SaveError::NotASave);
+    }
+    let version = u16::from_le_bytes(read_array(&mut reader)?);
+    if version != SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+
+    let mut decoder = DeflateDecoder::new(reader);
+    let settings = String::from_utf8(read_bytes(&mut decoder)?)
+        .map_err(|_| SaveError::Corrupted("settings are not UTF-8"))?;
+    let settings: Settings = toml::from_str(&settings).map_err(SaveError::Settings)?;
+    let errors = check_settings(&settings);
+    if !errors.is_empty() {
+        return Err(SaveError::InvalidSettings(errors));
+    }
+    let width = u32::from_le_bytes(read_array(&mut decoder)?);
+    let height = u32::from_le_bytes(read_array(&mut decoder)?);
+    check_size(
+        &World {
+            width,
+            height,
+            ..World::default()
+        },
+        &settings,
+    )?;
+    let count = width as usize * height as usize;
+
+    let biome_count = u32::from_le_bytes(read_array(&mut decoder)?);
+    let biome_names = (0..biome_count)
+        .map(|_| {
+            String::from_utf8(read_bytes(&mut decoder)?)
+                .map_err(|_| SaveError::Corrupted("biome name is not UTF-8"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut read_f64s = || {
+        (0..count)
+            .map(|_| Ok(f64::from_le_bytes(read_array(&mut decoder)?)))
+            .collect::<Result<Vec<_>, SaveError>>()
+    };
+    let elevation = read_f64s()?;
+    let temperature = read_f64s()?;
+    let biome = (0..count)
+        .map(|_| match u16::from_le_bytes(read_array(&mut decoder)?) {
+            NO_BIOME => Ok(None),
+            id if (id as usize) < biome_names.len() => Ok(Some(id)),
+            _ => Err(SaveError::Corrupted("unknown biome id")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if decoder.read(&mut [0])? != 0 {
+        return Err(SaveError::Corrupted("trailing data"));
+    }
+
+    let world = World {
+        width,
+        height,
+        elevation,
+        temperature,
+        biome,
+        biome_names,
+    };
+    Ok((settings, world))
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes(reader: &mut impl Read) -> Result<Vec<u8>, SaveError> {
+    let len = u32::from_le_bytes(read_array(reader)?);
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(SaveError::Corrupted("truncated"));
+    }
+    Ok(bytes)
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], SaveError> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use crate::worlds::map::biomes::{load_biomes, BIOMES_PATH};
+    use crate::worlds::map::generator::world::{World, WorldGenerator};
+    use crate::worlds::settings::Settings;
+
+    use super::{encode_world, read_world, save_world, write_world, SaveError, MAGIC};
+
+    fn saved_world() -> (Settings, World, Vec<u8>) {
+        let settings = Settings {
+            width: 12,
+            height: 8,
+            ..Settings::from_seed(1234)
+        };
+        let biomes = load_biomes(Path::new(BIOMES_PATH)).unwrap();
+        let world = WorldGenerator::new(settings.clone())
+            .with_biomes(biomes.into_values())
+            .generate()
+            .unwrap();
+
+        let mut bytes = Vec::new();
+        write_world(&world, &settings, &mut bytes).unwrap();
+        (settings, world, bytes)
+    }
+
+    #[rstest]
+    fn test_round_trip() {
+        let (settings, world, bytes) = saved_world();
+
+        let loaded = read_world(bytes.as_slice()).unwrap();
+
+        assert_eq!(loaded, (settings, world));
+    }
+
+    #[rstest]
+    fn test_not_a_save() {
+        assert!(matches!(
+            read_world(&b"name = \"Ocean\""[..]),
+            Err(SaveError::NotASave)
+        ));
+    }
+
+    #[rstest]
+    fn test_unsupported_version() {
+        let (_, _, mut bytes) = saved_world();
+        bytes[MAGIC.len()] = 0;
+
+        assert!(matches!(
+            read_world(bytes.as_slice()),
+            Err(SaveError::UnsupportedVersion(0))
+        ));
+    }
+
+    #[rstest]
+    fn test_size_mismatch() {
+        let (settings, world, _) = saved_world();
+        let mut bytes = Vec::new();
+        let wider = Settings {
+            width: 13,
+            ..settings
+        };
+        let mismatch = |result: Result<_, SaveError>| {
+            matches!(
+                result,
+                Err(SaveError::SizeMismatch {
+                    world: (12, 8),
+                    settings: (13, 8)
+                })
+            )
+        };
+
+        assert!(mismatch(write_world(&world, &wider, &mut bytes)));
+        assert!(bytes.is_empty());
+        encode_world(&world, &wider, &mut bytes).unwrap();
+        assert!(mismatch(read_world(bytes.as_slice()).map(|_| ())));
+    }
+
+    #[rstest]
+    fn test_save_ungenerated_world() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("world.sav");
+
+        let result = save_world(&World::default(), &Settings::from_seed(1), &path);
+
+        assert!(matches!(result, Err(SaveError::SizeMismatch { .. })));
+        assert!(!path.exists());
+    }
+
+    #[rstest]
+    fn test_invalid_settings() {
+        let settings = Settings {
+            width: 0,
+            ..Settings::from_seed(1)
+        };
+        let world = World {
+            width: 0,
+            height: settings.height,
+            elevation: Vec::new(),
+            temperature: Vec::new(),
+            biome: Vec::new(),
+            biome_names: Vec::new(),
+        };
+        let mut bytes = Vec::new();
+        write_world(&world, &settings, &mut bytes).unwrap();
+
+        assert!(matches!(
+            read_world(bytes.as_slice()),
+            Err(SaveError::InvalidSettings(_))
+        ));
+    }
+
+    #[rstest]
+    fn test_truncated() {
+        let (_, _, bytes) = saved_world();
+
+        assert!(matches!(
+            read_world(&bytes[..bytes.len() / 2]),
+            Err(SaveError::Corrupted(_))
+        ));
+    }
+}
@@ -0,0 +1,88 @@
+use bevy::math::DVec2;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Independent streams of randomness derived from the world seed.
+///
+/// Each generation step draws from its own stream so that adding draws to one
+/// step never shifts the values of another. Discriminants are part of the
+/// seeding scheme: never reorder them, only append new variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeedStream {
+    Elevation = 1,
+    Temperature = 2,
+    Shapes = 3,
+}
+
+/// Seeded RNG for the given stream, reproducible across runs and platforms.
+pub fn rng(world_seed: u64, stream: SeedStream) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(world_seed);
+    rng.set_stream(stream as u64);
+    rng
+}
+
+/// Seed and sampling offset of a noise function, derived from the world seed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseSeed {
+    pub seed: u32,
+    pub offset: DVec2,
+}
+
+impl NoiseSeed {
+    pub fn derive(world_seed: u64, stream: SeedStream) -> Self {
+        let mut rng = rng(world_seed, stream);
+        Self {
+            seed: rng.gen(),
+            offset: DVec2::new(
+                rng.gen_range(-100000..100000) as f64,
+                rng.gen_range(-100000..100000) as f64,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use rstest::rstest;
+
+    use super::{rng, NoiseSeed, SeedStream};
+
+    #[rstest]
+    #[case::elevation(SeedStream::Elevation)]
+    #[case::temperature(SeedStream::Temperature)]
+    #[case::shapes(SeedStream::Shapes)]
+    fn test_same_seed_same_values(#[case] stream: SeedStream) {
+        let a: Vec<u64> = rng(42, stream)
+            .sample_iter(rand::distributions::Standard)
+            .take(8)
+            .collect();
+        let b: Vec<u64> = rng(42, stream)
+            .sample_iter(rand::distributions::Standard)
+            .take(8)
+            .collect();
+
+        assert_eq!(a, b);
+        assert_eq!(NoiseSeed::derive(42, stream), NoiseSeed::derive(42, stream));
+    }
+
+    #[rstest]
+    fn test_streams_are_independent() {
+        let elevation = NoiseSeed::derive(42, SeedStream::Elevation);
+        let temperature = NoiseSeed::derive(42, SeedStream::Temperature);
+
+        assert_ne!(elevation, temperature);
+    }
+
+    #[rstest]
+    fn test_stable_across_platforms() {
+        // ChaCha output is specified bit for bit, these values must never change
+        // or previously shared seeds would produce different worlds.
+        let mut rng = rng(0, SeedStream::Elevation);
+        let values: [u32; 3] = [rng.gen(), rng.gen(), rng.gen()];
+
+        assert_eq!(values, GOLDEN_STREAM);
+    }
+
+    const GOLDEN_STREAM: [u32; 3] = [3369373459, 3244981007, 1867878502];
+}
@@ -1,94 +1,482 @@
 use bevy::prelude::*;
-use bevy_inspector_egui::quick::ResourceInspectorPlugin;
-use bevy_inspector_egui::InspectorOptions;
-use rand::{random, Rng};
-use std::collections::HashMap;
+use bevy_inspector_egui::prelude::*;
+use rand::random;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{read_dir, read_to_string, write};
 use std::path::{Path, PathBuf};
+use std::{fmt, io};
 
 use crate::worlds::map::biomes::Biome;
+use crate::worlds::map::generator::steps::{default_steps, validate_steps, Step, StepError};
+use crate::worlds::projection::Projection;
+use crate::worlds::sphere::MAX_SPHERE_SUBDIVISIONS;
+use crate::worlds::WorldsAssets;
 
 use super::map::biomes::load_biomes;
 
+pub mod world_code;
+
+pub const DEFAULT_SETTINGS_PATH: &str = "settings.toml";
+pub const PRESETS_PATH: &str = "assets/presets";
+/// Fewest tiles along each axis of the map, so that its edges are distinct.
+pub const MIN_MAP_SIZE: u32 = 2;
+
 pub(super) fn plugin(app: &mut App) {
+    let path = app
+        .world_mut()
+        .get_resource_or_insert_with(SettingsPath::default)
+        .0
+        .clone();
+    if !app.world().contains_resource::<Settings>() && path.is_file() {
+        match load_settings(&path) {
+            Ok(settings) => {
+                app.insert_resource(settings);
+            }
+            Err(e) => eprintln!("Error loading settings {:?}: {}", path, e),
+        }
+    }
+
     app.init_resource::<Settings>()
+        .init_resource::<Presets>()
         .register_type::<Settings>()
-        .add_plugins(ResourceInspectorPlugin::<Settings>::new());
+        .add_systems(Startup, setup_presets);
+}
+
+/// File the settings are loaded from at startup and saved to from the UI.
+#[derive(Resource)]
+pub struct SettingsPath(pub PathBuf);
+
+impl Default for SettingsPath {
+    fn default() -> Self {
+        Self(PathBuf::from(DEFAULT_SETTINGS_PATH))
+    }
+}
+
+/// Presets by name, they describe a kind of world and are applied without their seed.
+#[derive(Resource, Default)]
+pub struct Presets(pub BTreeMap<String, Settings>);
+
+fn setup_presets(mut presets: ResMut<Presets>, assets: Res<WorldsAssets>) {
+    match load_presets(&assets.presets) {
+        Ok(loaded) => presets.0 = loaded,
+        Err(e) => eprintln!("Error loading presets: {}", e),
+    }
 }
 
 // TBD: Condition the use of InspectorOptions
-#[derive(Reflect, Resource, InspectorOptions)]
+#[derive(Reflect, Resource, InspectorOptions, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[reflect(InspectorOptions)]
+#[serde(default)]
 pub struct Settings {
+    /// Every random value of the world is derived from this seed. Named so
+    /// that it is not taken for the seed of a noise, `seed` in older files.
+    #[serde(with = "seed_as_i64", alias = "seed")]
+    pub world_seed: u64,
+    #[inspector(min = MIN_MAP_SIZE)]
     pub height: u32,
+    #[inspector(min = MIN_MAP_SIZE)]
     pub width: u32,
     pub tile_size: Vec2,
+    /// Whether the east and west edges meet, like around a globe.
+    pub wrap: bool,
+    /// How longitude and latitude are laid out on the map.
+    pub projection: Projection,
+    /// Whether the world is generated on a sphere, then projected on the map.
+    pub sphere: bool,
+    /// Subdivisions of the icosahedron the sphere is built from, each one
+    /// quadruples its cells.
+    #[inspector(min = 0, max = MAX_SPHERE_SUBDIVISIONS)]
+    pub sphere_subdivisions: u32,
+    /// Open world: chunks are generated around the camera, without bounds,
+    /// instead of a map of `width` × `height` tiles.
+    pub streaming: bool,
 
     pub elevation: bool,
     pub temperature: bool,
     pub temperature_factor: f32,
+    /// How the map is drawn on screen.
+    pub render_mode: RenderMode,
 
     pub elevation_gen: PerlinConfiguration,
     pub temperature_gen: TemperatureGeneration,
+
+    /// What generates the world, in order, as `[[steps]]` tables.
+    #[reflect(ignore)]
+    pub steps: Vec<Step>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
-        let mut rng = rand::thread_rng();
+        Self::from_seed(random())
+    }
+}
+
+impl Settings {
+    pub fn from_seed(seed: u64) -> Self {
         Self {
+            world_seed: seed,
             height: 500,
             width: 500,
             tile_size: Vec2::new(50., 58.),
+            wrap: false,
+            projection: Projection::default(),
+            sphere: false,
+            sphere_subdivisions: 6,
+            streaming: false,
             elevation: true,
             temperature: false,
             temperature_factor: 0.4,
+            render_mode: RenderMode::default(),
             elevation_gen: PerlinConfiguration {
-                seed: random(),
                 noise_scale: 100.,
                 octaves: 4,
                 lacunarity: 2.5,
                 persistance: 0.5,
-                offset: Vec2::new(
-                    rng.gen_range(-100000..100000) as f32,
-                    rng.gen_range(-100000..100000) as f32,
-                ),
+                offset: Vec2::ZERO,
             },
             temperature_gen: TemperatureGeneration {
                 perlin: PerlinConfiguration {
-                    seed: random(),
                     noise_scale: 200.,
                     octaves: 3,
                     lacunarity: 4.,
                     persistance: 0.3,
-                    offset: Vec2::new(
-                        rng.gen_range(-100000..100000) as f32,
-                        rng.gen_range(-100000..100000) as f32,
-                    ),
+                    offset: Vec2::ZERO,
                 },
                 scale_lat_factor: 40.,
                 noise_factor: 20.,
             },
+            steps: default_steps(),
         }
     }
 }
 
+impl Settings {
+    /// Whether both settings generate the same world, they may still draw it
+    /// differently.
+    pub fn same_world(&self, other: &Settings) -> bool {
+        let drawn_like_other = Settings {
+            tile_size: other.tile_size,
+            elevation: other.elevation,
+            temperature: other.temperature,
+            temperature_factor: other.temperature_factor,
+            render_mode: other.render_mode,
+            streaming: other.streaming,
+            ..self.clone()
+        };
+        drawn_like_other == *other
+    }
+}
+
+#[derive(Reflect, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderMode {
+    /// A tile entity per tile, drawn by `bevy_ecs_tilemap`.
+    #[default]
+    Tilemap,
+    /// The tiles rasterised on the CPU into a single image, shown as a sprite.
+    Image,
+}
+
 #[derive(Reflect)]
 pub enum MapMode {
     Elevation,
     Temperature,
 }
 
-#[derive(Reflect)]
+#[derive(Reflect, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TemperatureGeneration {
     pub perlin: PerlinConfiguration,
     pub scale_lat_factor: f64,
     pub noise_factor: f64,
 }
 
-#[derive(Reflect)]
+#[derive(Reflect, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PerlinConfiguration {
-    pub seed: u32,
     pub noise_scale: f64,
     pub octaves: i32,
     pub lacunarity: f64,
     pub persistance: f64,
+    /// Added to the offset derived from the world seed.
     pub offset: Vec2,
 }
+
+/// A setting that would not generate a world.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidSetting {
+    /// A value out of its range, by key and table (`None` for the top level).
+    Value {
+        table: Option<&'static str>,
+        key: &'static str,
+        message: String,
+    },
+    Step(StepError),
+}
+
+impl fmt::Display for InvalidSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidSetting::Value {
+                table: Some(table),
+                key,
+                message,
+            } => write!(f, "`{}.{}` {}", table, key, message),
+            InvalidSetting::Value {
+                table: None,
+                key,
+                message,
+            } => write!(f, "`{}` {}", key, message),
+            InvalidSetting::Step(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Checks every setting the generation relies on: a map with tiles, a sphere
+/// that fits in memory, usable noises and steps that would run. Files, world
+/// codes, saves and `worlds-cli validate` all go through it.
+pub fn check_settings(settings: &Settings) -> Vec<InvalidSetting> {
+    let mut errors = Vec::new();
+    let mut check = |valid: bool, table: Option<&'static str>, key: &'static str, message: &str| {
+        if !valid {
+            errors.push(InvalidSetting::Value {
+                table,
+                key,
+                message: message.to_string(),
+            });
+        }
+    };
+
+    let min_size = format!("must be at least {}", MIN_MAP_SIZE);
+    check(settings.width >= MIN_MAP_SIZE, None, "width", &min_size);
+    check(settings.height >= MIN_MAP_SIZE, None, "height", &min_size);
+    check(
+        settings.tile_size.x > 0. && settings.tile_size.y > 0.,
+        None,
+        "tile_size",
+        "must be positive",
+    );
+    check(
+        settings.sphere_subdivisions <= MAX_SPHERE_SUBDIVISIONS,
+        None,
+        "sphere_subdivisions",
+        &format!("must be at most {}", MAX_SPHERE_SUBDIVISIONS),
+    );
+    check(
+        (0. ..=1.).contains(&settings.temperature_factor),
+        None,
+        "temperature_factor",
+        "must be between 0 and 1",
+    );
+    for (table, perlin) in [
+        ("elevation_gen", &settings.elevation_gen),
+        ("temperature_gen.perlin", &settings.temperature_gen.perlin),
+    ] {
+        let table = Some(table);
+        check(
+            perlin.noise_scale > 0.,
+            table,
+            "noise_scale",
+            "must be positive",
+        );
+        check(perlin.octaves > 0, table, "octaves", "must be positive");
+        check(
+            perlin.lacunarity > 0.,
+            table,
+            "lacunarity",
+            "must be positive",
+        );
+        check(
+            perlin.persistance > 0.,
+            table,
+            "persistance",
+            "must be positive",
+        );
+    }
+
+    errors.extend(
+        validate_steps(&settings.steps)
+            .into_iter()
+            .map(InvalidSetting::Step),
+    );
+    errors
+}
+
+#[derive(Debug)]
+pub enum SettingsFileError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    Invalid(Vec<InvalidSetting>),
+}
+
+impl fmt::Display for SettingsFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsFileError::Io(e) => write!(f, "File error: {}", e),
+            SettingsFileError::Parse(e) => write!(f, "TOML parse error: {}", e),
+            SettingsFileError::Serialize(e) => write!(f, "TOML serialize error: {}", e),
+            SettingsFileError::Invalid(errors) => {
+                let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+                write!(f, "Invalid settings: {}", errors.join(", "))
+            }
+        }
+    }
+}
+
+/// Fields missing from the file take their default value, a missing seed is random.
+/// Settings failing [`check_settings`] are rejected.
+pub fn load_settings(path: &Path) -> Result<Settings, SettingsFileError> {
+    let contents = read_to_string(path).map_err(SettingsFileError::Io)?;
+    let settings = parse_settings(&contents).map_err(SettingsFileError::Parse)?;
+    let errors = check_settings(&settings);
+    if !errors.is_empty() {
+        return Err(SettingsFileError::Invalid(errors));
+    }
+    Ok(settings)
+}
+
+pub fn parse_settings(contents: &str) -> Result<Settings, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+pub fn save_settings(settings: &Settings, path: &Path) -> Result<(), SettingsFileError> {
+    let contents = toml::to_string(settings).map_err(SettingsFileError::Serialize)?;
+    write(path, contents).map_err(SettingsFileError::Io)
+}
+
+/// Loads every preset of a directory, named after their file stem.
+pub fn load_presets(path: &Path) -> Result<BTreeMap<String, Settings>, io::Error> {
+    let mut presets = BTreeMap::new();
+
+    for entry in read_dir(path)? {
+        let path = entry?.path();
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        match load_settings(&path) {
+            Ok(settings) => {
+                presets.insert(name.to_string(), settings);
+            }
+            Err(e) => eprintln!("Error loading preset {:?}: {}", path, e),
+        }
+    }
+
+    Ok(presets)
+}
+
+/// TOML integers are signed, the seed is stored with the same bits as an `i64`.
+mod seed_as_i64 {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(seed: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(*seed as i64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        i64::deserialize(deserializer).map(|seed| seed as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::read_dir, path::Path};
+
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use super::{load_presets, load_settings, save_settings, RenderMode, Settings, PRESETS_PATH};
+
+    #[rstest]
+    fn test_save_load_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        let mut settings = Settings::from_seed(u64::MAX);
+        settings.temperature = true;
+
+        save_settings(&settings, &path).expect("Settings should be saved");
+        let loaded = load_settings(&path).expect("Saved settings should load");
+
+        assert_eq!(loaded, settings);
+    }
+
+    #[rstest]
+    fn test_load_partial_settings() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, "seed = 42\nwidth = 100\n").unwrap();
+
+        let loaded = load_settings(&path).expect("Partial settings should load");
+
+        assert_eq!(loaded.world_seed, 42);
+        assert_eq!(loaded.width, 100);
+        assert_eq!(loaded.height, Settings::from_seed(42).height);
+    }
+
+    #[rstest]
+    fn test_load_invalid_steps() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, "[[steps]]\nkind = \"shape\"\nstrength = 0.5\n").unwrap();
+
+        let error = load_settings(&path).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid settings: Step 1: `shape` needs `elevation`, which no previous step outputs"
+        );
+    }
+
+    #[rstest]
+    fn test_load_too_many_sphere_subdivisions() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, "sphere_subdivisions = 32\n").unwrap();
+
+        let error = load_settings(&path).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid settings: `sphere_subdivisions` must be at most 9"
+        );
+    }
+
+    #[rstest]
+    #[case::empty("width = 0\n")]
+    #[case::single_column("width = 1\n")]
+    fn test_load_too_small_map(#[case] contents: &str) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(&path, contents).unwrap();
+
+        let error = load_settings(&path).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid settings: `width` must be at least 2"
+        );
+    }
+
+    #[rstest]
+    #[case::drawing(|s: &mut Settings| s.temperature_factor = 0.9, true)]
+    #[case::layers(|s: &mut Settings| s.temperature = !s.temperature, true)]
+    #[case::streaming(|s: &mut Settings| s.streaming = true, true)]
+    #[case::render_mode(|s: &mut Settings| s.render_mode = RenderMode::Image, true)]
+    #[case::seed(|s: &mut Settings| s.world_seed += 1, false)]
+    #[case::noise(|s: &mut Settings| s.elevation_gen.octaves += 1, false)]
+    fn test_same_world(#[case] change: fn(&mut Settings), #[case] same: bool) {
+        let settings = Settings::from_seed(42);
+        let mut changed = settings.clone();
+        change(&mut changed);
+
+        assert_eq!(settings.same_world(&changed), same);
+    }
+
+    #[rstest]
+    fn test_load_shipped_presets() {
+        let presets = load_presets(Path::new(PRESETS_PATH)).expect("Presets directory exists");
+        let files = read_dir(PRESETS_PATH).unwrap().count();
+
+        // Invalid presets are skipped, every shipped one must load
+        assert_eq!(presets.len(), files);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use super::{check_settings, InvalidSetting, Settings};
+
+/// First byte of every world code, bump it whenever the encoded layout changes.
+const WORLD_CODE_VERSION: u8 = 1;
+
+/// Longest settings TOML a world code may inflate to, far more than any
+/// settings need, so that a crafted code cannot exhaust the memory.
+const MAX_DECODED_LEN: u64 = 1 << 20;
+
+#[derive(Debug)]
+pub enum WorldCodeError {
+    Base64(base64::DecodeError),
+    Empty,
+    UnsupportedVersion(u8),
+    TooLarge,
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Invalid(Vec<InvalidSetting>),
+}
+
+impl fmt::Display for WorldCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldCodeError::Base64(e) => write!(f, "Invalid world code: {}", e),
+            WorldCodeError::Empty => write!(f, "Empty world code"),
+            WorldCodeError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported world code version: {}", v)
+            }
+            WorldCodeError::TooLarge => write!(
+                f,
+                "World code settings are larger than {} bytes",
+                MAX_DECODED_LEN
+            ),
+            WorldCodeError::Io(e) => write!(f, "Corrupted world code: {}", e),
+            WorldCodeError::Toml(e) => write!(f, "Invalid settings in world code: {}", e),
+            WorldCodeError::Invalid(errors) => {
+                let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+                write!(f, "Invalid settings in world code: {}", errors.join(", "))
+            }
+        }
+    }
+}
+
+/// Encodes the settings as a version byte followed by their deflated TOML,
+/// in URL-safe base64 so the code survives being pasted anywhere.
+pub fn encode(settings: &Settings) -> String {
+    let toml = toml::to_string(settings).expect("Settings should serialize to TOML");
+
+    let mut encoder = DeflateEncoder::new(vec![WORLD_CODE_VERSION], Compression::best());
+    encoder
+        .write_all(toml.as_bytes())
+        .expect("Writing to memory should not fail");
+    let bytes = encoder.finish().expect("Writing to memory should not fail");
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Decodes settings, rejecting the ones failing [`check_settings`] like a
+/// settings file.
+pub fn decode(code: &str) -> Result<Settings, WorldCodeError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(code.trim())
+        .map_err(WorldCodeError::Base64)?;
+    let (&version, compressed) = bytes.split_first().ok_or(WorldCodeError::Empty)?;
+    if version != WORLD_CODE_VERSION {
+        return Err(WorldCodeError::UnsupportedVersion(version));
+    }
+
+    // One byte past the limit tells a code at the limit from a larger one
+    let mut toml = Vec::new();
+    DeflateDecoder::new(compressed)
+        .take(MAX_DECODED_LEN + 1)
+        .read_to_end(&mut toml)
+        .map_err(WorldCodeError::Io)?;
+    if toml.len() as u64 > MAX_DECODED_LEN {
+        return Err(WorldCodeError::TooLarge);
+    }
+    let toml = String::from_utf8(toml)
+        .map_err(|e| WorldCodeError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+
+    let settings: Settings = toml::from_str(&toml).map_err(WorldCodeError::Toml)?;
+    let errors = check_settings(&settings);
+    if !errors.is_empty() {
+        return Err(WorldCodeError::Invalid(errors));
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use flate2::{write::DeflateEncoder, Compression};
+    use rstest::rstest;
+
+    use crate::worlds::settings::Settings;
+
+    use super::{decode, encode, WorldCodeError, MAX_DECODED_LEN, WORLD_CODE_VERSION};
+
+    #[rstest]
+    #[case::small_seed(0)]
+    #[case::large_seed(u64::MAX)]
+    fn test_round_trip(#[case] seed: u64) {
+        let mut settings = Settings::from_seed(seed);
+        settings.width = 123;
+        settings.temperature = true;
+        settings.elevation_gen.octaves = 7;
+
+        let decoded = decode(&encode(&settings)).expect("Encoded settings should decode");
+
+        assert_eq!(decoded, settings);
+    }
+
+    #[rstest]
+    fn test_decode_ignores_surrounding_whitespace() {
+        let settings = Settings::from_seed(42);
+        let code = format!("  {}\n", encode(&settings));
+
+        assert_eq!(decode(&code).unwrap(), settings);
+    }
+
+    #[rstest]
+    #[case::not_base64("not a world code!")]
+    #[case::empty("")]
+    #[case::garbage("AQID")]
+    fn test_decode_invalid(#[case] code: &str) {
+        assert!(decode(code).is_err());
+    }
+
+    #[rstest]
+    fn test_decode_unsupported_version() {
+        let mut bytes = URL_SAFE_NO_PAD
+            .decode(encode(&Settings::from_seed(42)))
+            .unwrap();
+        bytes[0] = 0;
+
+        assert!(matches!(
+            decode(&URL_SAFE_NO_PAD.encode(bytes)),
+            Err(WorldCodeError::UnsupportedVersion(0))
+        ));
+    }
+
+    #[rstest]
+    fn test_decode_empty_map() {
+        let settings = Settings {
+            width: 0,
+            ..Settings::from_seed(42)
+        };
+
+        assert_eq!(
+            decode(&encode(&settings)).unwrap_err().to_string(),
+            "Invalid settings in world code: `width` must be at least 2"
+        );
+    }
+
+    #[rstest]
+    fn test_decode_too_large() {
+        // Spaces deflate to almost nothing, but must not be inflated past the limit
+        let mut encoder = DeflateEncoder::new(vec![WORLD_CODE_VERSION], Compression::best());
+        encoder
+            .write_all(&vec![b' '; MAX_DECODED_LEN as usize + 1])
+            .unwrap();
+        let code = URL_SAFE_NO_PAD.encode(encoder.finish().unwrap());
+
+        assert!(matches!(decode(&code), Err(WorldCodeError::TooLarge)));
+    }
+}
@@ -0,0 +1,187 @@
+//! Hexagonal grid on a sphere: the cells of a Goldberg polyhedron, centred on
+//! the vertices of a subdivided icosahedron. The 12 vertices of the
+//! icosahedron are pentagons, every other cell is a hexagon.
+
+use std::collections::HashMap;
+
+use bevy::math::DVec3;
+
+/// Beyond it the grid has millions of cells, more than any map can show.
+pub const MAX_SPHERE_SUBDIVISIONS: u32 = 9;
+
+pub struct SphereGrid {
+    /// Centres of the cells, on the unit sphere.
+    pub cells: Vec<DVec3>,
+    /// Indices of the 5 or 6 neighbours of each cell.
+    pub neighbours: Vec<Vec<usize>>,
+}
+
+impl SphereGrid {
+    /// Halves the edges of the icosahedron `subdivisions` times, which makes
+    /// `10 * 4^subdivisions + 2` cells.
+    pub fn new(subdivisions: u32) -> Self {
+        let phi = (1. + 5f64.sqrt()) / 2.;
+        let mut cells: Vec<DVec3> = [
+            (-1., phi, 0.),
+            (1., phi, 0.),
+            (-1., -phi, 0.),
+            (1., -phi, 0.),
+            (0., -1., phi),
+            (0., 1., phi),
+            (0., -1., -phi),
+            (0., 1., -phi),
+            (phi, 0., -1.),
+            (phi, 0., 1.),
+            (-phi, 0., -1.),
+            (-phi, 0., 1.),
+        ]
+        .into_iter()
+        .map(|(x, y, z)| DVec3::new(x, y, z).normalize())
+        .collect();
+        let mut faces: Vec<[usize; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut midpoint = |a: usize, b: usize, cells: &mut Vec<DVec3>| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    cells.push(((cells[a] + cells[b]) / 2.).normalize());
+                    cells.len() - 1
+                })
+            };
+
+            faces = faces
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let ab = midpoint(a, b, &mut cells);
+                    let bc = midpoint(b, c, &mut cells);
+                    let ca = midpoint(c, a, &mut cells);
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut neighbours = vec![Vec::with_capacity(6); cells.len()];
+        for [a, b, c] in faces {
+            for (from, to) in [(a, b), (b, c), (c, a), (b, a), (c, b), (a, c)] {
+                if !neighbours[from].contains(&to) {
+                    neighbours[from].push(to);
+                }
+            }
+        }
+
+        Self { cells, neighbours }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Cell around a position of the unit sphere, walking the grid from
+    /// `start`: the closer `start`, the faster.
+    ///
+    /// The subdivided triangles are not exactly Delaunay, so the walk looks two
+    /// cells away before stopping in a local minimum.
+    pub fn nearest(&self, position: DVec3, start: usize) -> usize {
+        let closeness = |cell: usize| self.cells[cell].dot(position);
+        let mut cell = start;
+        loop {
+            let closest = self.neighbours[cell]
+                .iter()
+                .flat_map(|&neighbour| {
+                    std::iter::once(neighbour).chain(self.neighbours[neighbour].iter().copied())
+                })
+                .max_by(|&a, &b| closeness(a).total_cmp(&closeness(b)))
+                .filter(|&closest| closeness(closest) > closeness(cell));
+            match closest {
+                Some(closest) => cell = closest,
+                None => return cell,
+            }
+        }
+    }
+}
+
+/// Longitude and latitude, in degrees, of a position of the unit sphere.
+pub fn to_lonlat(position: DVec3) -> (f64, f64) {
+    let lon = position.y.atan2(position.x).to_degrees();
+    let lat = position.z.clamp(-1., 1.).asin().to_degrees();
+    (lon, lat)
+}
+
+pub fn from_lonlat(lon: f64, lat: f64) -> DVec3 {
+    let (lon, lat) = (lon.to_radians(), lat.to_radians());
+    DVec3::new(lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{from_lonlat, to_lonlat, SphereGrid};
+
+    #[rstest]
+    #[case::icosahedron(0)]
+    #[case::subdivided(3)]
+    fn test_goldberg_cells(#[case] subdivisions: u32) {
+        let grid = SphereGrid::new(subdivisions);
+
+        assert_eq!(grid.len(), 10 * 4usize.pow(subdivisions) + 2);
+        let pentagons = grid.neighbours.iter().filter(|n| n.len() == 5).count();
+        let hexagons = grid.neighbours.iter().filter(|n| n.len() == 6).count();
+        assert_eq!(pentagons, 12);
+        assert_eq!(pentagons + hexagons, grid.len());
+        for (cell, neighbours) in grid.neighbours.iter().enumerate() {
+            assert!((grid.cells[cell].length() - 1.).abs() < 1e-12);
+            for &neighbour in neighbours {
+                assert!(grid.neighbours[neighbour].contains(&cell));
+            }
+        }
+    }
+
+    #[rstest]
+    fn test_nearest() {
+        let grid = SphereGrid::new(3);
+
+        for (cell, &position) in grid.cells.iter().enumerate() {
+            assert_eq!(grid.nearest(position, 0), cell);
+        }
+        // Halfway to a neighbour, slightly on its side
+        let (a, b) = (grid.cells[0], grid.cells[grid.neighbours[0][0]]);
+        let position = (a * 0.45 + b * 0.55).normalize();
+        assert_eq!(grid.nearest(position, 100), grid.neighbours[0][0]);
+    }
+
+    #[rstest]
+    #[case(0., 0.)]
+    #[case(-120., 45.)]
+    #[case(170., -80.)]
+    fn test_lonlat_round_trip(#[case] lon: f64, #[case] lat: f64) {
+        let (round_lon, round_lat) = to_lonlat(from_lonlat(lon, lat));
+
+        assert!((round_lon - lon).abs() < 1e-9 && (round_lat - lat).abs() < 1e-9);
+    }
+}
@@ -1,16 +1,287 @@
 use bevy::{input::mouse::MouseWheel, prelude::*};
 use bevy_inspector_egui::{
-    bevy_egui::{self, EguiPlugin},
+    bevy_egui::{self, egui, EguiPlugin},
     quick::ResourceInspectorPlugin,
     DefaultInspectorConfigPlugin,
 };
 
+use super::export::{
+    biome_colors, export_world,
+    tiled::{export_tiled, import_tiled, TILED_MAP_FILE},
+    ExportError, Rasterisation, EXPORT_PATH, MAX_HEX_TILE_WIDTH, MIN_HEX_TILE_WIDTH,
+};
+use super::map::{
+    biomes::Biomes,
+    generator::{biome::TileBiome, elevation::TileElevation, temperature::TileTemperature},
+    inspect::{inspect_tile, InspectedTile, TileInspection},
+    layers::LayerGrid,
+    GeneratedWorld, GenerationTask, MapState, PendingWorld,
+};
+use super::save::{load_world, save_world, DEFAULT_SAVE_PATH};
+use super::settings::{check_settings, save_settings, world_code, Presets, Settings, SettingsPath};
+
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((EguiPlugin, DefaultInspectorConfigPlugin))
-        .add_systems(
-            PreUpdate,
-            (absorb_egui_inputs.after(bevy_egui::systems::process_input_system),),
-        );
+    app.add_systems(Update, (world_window, tile_inspector));
+}
+
+pub(super) fn inspector_plugin(app: &mut App) {
+    app.add_plugins(ResourceInspectorPlugin::<Settings>::new());
+}
+
+/// Shared by the UI and the inspector, the host may already have added Egui.
+pub(super) fn egui_plugin(app: &mut App) {
+    if !app.is_plugin_added::<EguiPlugin>() {
+        app.add_plugins(EguiPlugin);
+    }
+    if !app.is_plugin_added::<DefaultInspectorConfigPlugin>() {
+        app.add_plugins(DefaultInspectorConfigPlugin);
+    }
+    app.add_systems(
+        PreUpdate,
+        (absorb_egui_inputs.after(bevy_egui::systems::process_input_system),),
+    );
+}
+
+fn world_window(
+    mut contexts: bevy_egui::EguiContexts,
+    mut settings: ResMut<Settings>,
+    settings_path: Res<SettingsPath>,
+    presets: Res<Presets>,
+    world: Res<GeneratedWorld>,
+    state: Res<State<MapState>>,
+    generation: Res<GenerationTask>,
+    mut pending_world: ResMut<PendingWorld>,
+    biomes: Res<Biomes>,
+    mut code: Local<String>,
+    mut hexagons: Local<Option<u32>>,
+    mut status: Local<Option<String>>,
+) {
+    egui::Window::new("World").show(contexts.ctx_mut(), |ui| {
+        let step = match state.get() {
+            MapState::Generating => Some("Generating"),
+            MapState::Classifying => Some("Classifying"),
+            _ => None,
+        };
+        if let Some(step) = step {
+            let fraction = generation.progress.fraction();
+            ui.add(
+                egui::ProgressBar::new(fraction)
+                    .text(format!("{} {:.0}%", step, fraction * 100.))
+                    .animate(true),
+            );
+            ui.separator();
+        }
+
+        // Only assign `settings` when needed: touching it triggers a regeneration
+        ui.horizontal(|ui| {
+            ui.label("Preset");
+            egui::ComboBox::from_id_salt("preset")
+                .selected_text("Select")
+                .show_ui(ui, |ui| {
+                    for (name, preset) in presets.0.iter() {
+                        if ui.selectable_label(false, name).clicked() {
+                            *settings = Settings {
+                                world_seed: settings.world_seed,
+                                ..preset.clone()
+                            };
+                        }
+                    }
+                });
+        });
+
+        if ui.button("Save settings").clicked() {
+            *status = Some(match save_settings(&settings, &settings_path.0) {
+                Ok(()) => format!("Settings saved to {:?}", settings_path.0),
+                Err(e) => e.to_string(),
+            });
+        }
+
+        ui.separator();
+        if ui.button("Copy world code").clicked() {
+            let world_code = world_code::encode(&settings);
+            ui.output_mut(|output| output.copied_text = world_code);
+        }
+        ui.add(egui::TextEdit::singleline(&mut *code).hint_text("World code"));
+        if ui.button("Paste world code").clicked() {
+            match world_code::decode(&code) {
+                Ok(decoded) => {
+                    *settings = decoded;
+                    *status = None;
+                }
+                Err(e) => *status = Some(e.to_string()),
+            }
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            let path = std::path::Path::new(DEFAULT_SAVE_PATH);
+            let ready = *state.get() == MapState::Ready;
+            if ui
+                .add_enabled(ready, egui::Button::new("Save world"))
+                .clicked()
+            {
+                *status = Some(match save_world(&world.0, &world.1, path) {
+                    Ok(()) => format!("World saved to {:?}", path),
+                    Err(e) => e.to_string(),
+                });
+            }
+            if ui.button("Load world").clicked() {
+                *status = Some(match load_world(path) {
+                    Ok((loaded_settings, loaded_world)) => {
+                        *settings = loaded_settings;
+                        pending_world.0 = Some(loaded_world);
+                        format!("World loaded from {:?}", path)
+                    }
+                    Err(e) => e.to_string(),
+                });
+            }
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            let mut hex_accurate = hexagons.is_some();
+            if ui.checkbox(&mut hex_accurate, "Hexagons").changed() {
+                *hexagons = hex_accurate.then_some(8);
+            }
+            if let Some(tile_width) = hexagons.as_mut() {
+                ui.add(
+                    egui::DragValue::new(tile_width)
+                        .range(MIN_HEX_TILE_WIDTH..=MAX_HEX_TILE_WIDTH)
+                        .suffix(" px"),
+                );
+            }
+        });
+        if ui.button("Export PNG").clicked() {
+            let rasterisation = match *hexagons {
+                Some(tile_width) => Rasterisation::Hexagons { tile_width },
+                None => Rasterisation::PixelPerTile,
+            };
+            let dir = std::path::Path::new(EXPORT_PATH);
+            *status = Some(
+                match export_world(&world.0, &world.1, &biomes.0, rasterisation, dir) {
+                    Ok(_) => format!("World exported to {:?}", dir),
+                    Err(e) => e.to_string(),
+                },
+            );
+        }
+
+        ui.horizontal(|ui| {
+            let dir = std::path::Path::new(EXPORT_PATH);
+            if ui.button("Export Tiled").clicked() {
+                let colors = biome_colors(&world.0, &biomes.0);
+                let exported = std::fs::create_dir_all(dir)
+                    .map_err(ExportError::Io)
+                    .and_then(|()| export_tiled(&world.0, &world.1, &colors, dir));
+                *status = Some(match exported {
+                    Ok(_) => format!("Tiled map exported to {:?}", dir),
+                    Err(e) => e.to_string(),
+                });
+            }
+            if ui.button("Import Tiled").clicked() {
+                *status = Some(match import_tiled(&dir.join(TILED_MAP_FILE)) {
+                    Ok(imported) => {
+                        // The settings are only replaced by valid ones
+                        let resized = Settings {
+                            width: imported.width,
+                            height: imported.height,
+                            ..settings.clone()
+                        };
+                        let errors: Vec<_> = check_settings(&resized)
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect();
+                        if errors.is_empty() {
+                            *settings = resized;
+                            pending_world.0 = Some(imported);
+                            format!("Tiled map imported from {:?}", dir)
+                        } else {
+                            format!("Invalid settings: {}", errors.join(", "))
+                        }
+                    }
+                    Err(e) => e.to_string(),
+                });
+            }
+        });
+
+        if let Some(status) = status.as_ref() {
+            ui.label(status);
+        }
+    });
+}
+
+/// Tooltip with the values of the hovered tile, and a window with those of
+/// the pinned tile.
+fn tile_inspector(
+    mut contexts: bevy_egui::EguiContexts,
+    settings: Res<Settings>,
+    world: Res<GeneratedWorld>,
+    elevation: Res<LayerGrid<TileElevation>>,
+    temperature: Res<LayerGrid<TileTemperature>>,
+    biome: Res<LayerGrid<TileBiome>>,
+    mut inspected: ResMut<InspectedTile>,
+) {
+    let inspect = |tile_pos| {
+        inspect_tile(
+            &settings,
+            &elevation,
+            &temperature,
+            &biome,
+            &world.0.biome_names,
+            tile_pos,
+        )
+    };
+    let ctx = contexts.ctx_mut();
+
+    if let Some(inspection) = inspected.hovered.and_then(inspect) {
+        if !ctx.is_pointer_over_area() {
+            egui::show_tooltip_at_pointer(
+                ctx,
+                egui::LayerId::background(),
+                egui::Id::new("tile_tooltip"),
+                |ui| tile_values(ui, &inspection),
+            );
+        }
+    }
+
+    if let Some(inspection) = inspected.pinned.and_then(inspect) {
+        let mut open = true;
+        egui::Window::new("Tile")
+            .open(&mut open)
+            .show(ctx, |ui| tile_values(ui, &inspection));
+        if !open {
+            inspected.pinned = None;
+        }
+    }
+}
+
+fn tile_values(ui: &mut egui::Ui, inspection: &TileInspection) {
+    let (lon, lat) = inspection.lonlat;
+    let rows = [
+        (
+            "Tile",
+            format!("{}, {}", inspection.tile_pos.x, inspection.tile_pos.y),
+        ),
+        ("Longitude", format!("{:.2}°", lon)),
+        ("Latitude", format!("{:.2}°", lat)),
+        ("Elevation", format!("{:.2}", inspection.elevation.0)),
+        ("Temperature", format!("{:.2}", inspection.temperature.0)),
+        (
+            "Biome",
+            inspection
+                .biome
+                .clone()
+                .unwrap_or_else(|| "None".to_string()),
+        ),
+    ];
+    egui::Grid::new("tile_values")
+        .num_columns(2)
+        .show(ui, |ui| {
+            for (name, value) in rows {
+                ui.label(name);
+                ui.label(value);
+                ui.end_row();
+            }
+        });
 }
 
 fn absorb_egui_inputs(
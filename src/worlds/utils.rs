@@ -9,24 +9,108 @@ pub fn scale_to_index(value: f64, min: f64, max: f64, scale_min: f64, scale_max:
 }
 
 pub fn xy_to_lonlat(config: &Settings, x: u32, y: u32) -> (f64, f64) {
-    let x_min = 0_f64;
-    let y_min = 0_f64;
-    let x_max = (config.width - 1) as f64;
-    let y_max = (config.height - 1) as f64;
+    position_to_lonlat(config, x as f64, y as f64)
+}
+
+/// Same as `xy_to_lonlat` for positions between tile centres.
+///
+/// Positions out of the projected globe take the longitude and latitude of
+/// its nearest edge, see [`tile_lonlat`] to tell them apart.
+pub fn position_to_lonlat(config: &Settings, x: f64, y: f64) -> (f64, f64) {
+    let (x, y) = normalized_position(config, x, y);
+    config.projection.inverse_clamped(x, y)
+}
+
+/// Longitude and latitude of a tile, `None` when out of the projected globe.
+pub fn tile_lonlat(config: &Settings, x: u32, y: u32) -> Option<(f64, f64)> {
+    let (x, y) = normalized_position(config, x as f64, y as f64);
+    config.projection.inverse(x, y)
+}
+
+/// Share of the globe covered by a tile, relative to the other tiles of the map.
+pub fn tile_area_weight(config: &Settings, x: u32, y: u32) -> f64 {
+    let (x, y) = normalized_position(config, x as f64, y as f64);
+    config.projection.area_weight(x, y)
+}
+
+pub fn lonlat_to_xy(config: &Settings, lon: f64, lat: f64) -> Option<(u32, u32)> {
+    let (x, y) = config.projection.forward(lon, lat)?;
+    let x = match config.wrap {
+        true => scale(x, -1., 1., 0., config.width as f64).round() as u32 % config.width.max(1),
+        false => scale(x, -1., 1., 0., axis_max(config.width)).round() as u32,
+    };
+
+    Some((
+        x,
+        scale(y, -1., 1., 0., axis_max(config.height)).round() as u32,
+    ))
+}
 
-    let lon = scale(x as f64, x_min, x_max, -180., 180.);
-    let lat = scale(y as f64, y_min, y_max, -90., 90.);
+/// Position on the map, from `[-1, 1]` on both axes, the edge tiles centred
+/// on the edges of the projection. When the world wraps, x goes from `-1` to
+/// just short of `1` instead, one column past the east edge being the west
+/// one like for the noise.
+fn normalized_position(config: &Settings, x: f64, y: f64) -> (f64, f64) {
+    let x = match config.wrap {
+        true => scale(x, 0., config.width.max(1) as f64, -1., 1.),
+        false => scale_axis(x, config.width),
+    };
 
-    (lon, lat)
+    (x, scale_axis(y, config.height))
 }
 
-pub fn lonlat_to_xy(config: &Settings, lon: f64, lat: f64) -> (u32, u32) {
-    let x_min = 0_f64;
-    let y_min = 0_f64;
-    let x_max = (config.width - 1) as f64;
-    let y_max = (config.height - 1) as f64;
-    let x = scale(lon, -180., 180., x_min, x_max);
-    let y = scale(lat, -90., 90., y_min, y_max);
+/// Index of the last tile along an axis of `size` tiles.
+fn axis_max(size: u32) -> f64 {
+    size.saturating_sub(1) as f64
+}
+
+/// Position along an axis of `size` tiles from `[-1, 1]`, its only tile in the
+/// middle when it has fewer than two, which settings checks reject.
+fn scale_axis(value: f64, size: u32) -> f64 {
+    match size {
+        0 | 1 => 0.,
+        _ => scale(value, 0., axis_max(size), -1., 1.),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::worlds::settings::Settings;
+
+    use super::{lonlat_to_xy, xy_to_lonlat};
+
+    #[rstest]
+    fn test_wrapped_columns_meet_once_at_the_seam() {
+        let settings = Settings {
+            width: 8,
+            height: 4,
+            wrap: true,
+            ..Settings::from_seed(0)
+        };
+
+        let longitudes: Vec<f64> = (0..settings.width)
+            .map(|x| xy_to_lonlat(&settings, x, 0).0)
+            .collect();
+        assert_eq!(longitudes[0], -180.);
+        // Columns are evenly spaced around the globe, the last one short of
+        // the first one
+        for (x, lon) in longitudes.iter().enumerate() {
+            assert!((lon - (-180. + 45. * x as f64)).abs() < 1e-9);
+        }
+        assert_eq!(lonlat_to_xy(&settings, 180., 0.).unwrap().0, 0);
+        assert_eq!(lonlat_to_xy(&settings, 135., 0.).unwrap().0, 7);
+    }
+
+    #[rstest]
+    fn test_single_tile_map_is_centred() {
+        let settings = Settings {
+            width: 1,
+            height: 1,
+            ..Settings::from_seed(0)
+        };
 
-    (x as u32, y as u32)
+        assert_eq!(xy_to_lonlat(&settings, 0, 0), (0., 0.));
+    }
 }
@@ -0,0 +1,398 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{read_dir, read_to_string},
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::map::biomes::{parse_biome, Biome, Condition};
+use super::map::generator::biome::layer_range;
+use super::settings::{check_settings, parse_settings, InvalidSetting, Settings};
+
+/// A problem found in a configuration file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub path: PathBuf,
+    /// Line and column, starting at 1, when the problem can be located.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.path.display(),
+                line,
+                column,
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Issues found by the successive validations.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn push(&mut self, path: &Path, location: Option<(usize, usize)>, message: String) {
+        self.issues.push(Issue {
+            path: path.to_path_buf(),
+            location,
+            message,
+        });
+    }
+
+    /// Checks a settings file, returning the settings when they could be read.
+    pub fn validate_settings(&mut self, path: &Path) -> Option<Settings> {
+        let contents = match read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                self.push(path, None, format!("File read error: {}", e));
+                return None;
+            }
+        };
+        let settings = match parse_settings(&contents) {
+            Ok(settings) => settings,
+            Err(e) => {
+                let location = e.span().map(|span| line_column(&contents, span.start));
+                self.push(path, location, e.message().to_string());
+                return None;
+            }
+        };
+
+        for error in check_settings(&settings) {
+            let (location, message) = match error {
+                InvalidSetting::Value {
+                    table,
+                    key,
+                    message,
+                } => (
+                    locate(&contents, table, key),
+                    format!("`{}` {}", key, message),
+                ),
+                InvalidSetting::Step(error) => (
+                    locate_array_table(&contents, "steps", error.index),
+                    error.message,
+                ),
+            };
+            self.push(path, location, message);
+        }
+
+        Some(settings)
+    }
+
+    /// Checks every file of a biomes directory.
+    ///
+    /// Format errors are reported for every biome, while conditions are only
+    /// checked against the layers of the settings for enabled biomes.
+    pub fn validate_biomes(&mut self, dir: &Path, settings: &Settings) -> io::Result<()> {
+        let mut paths = read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+
+        let mut names: HashMap<String, PathBuf> = HashMap::new();
+        let mut enabled: Vec<(PathBuf, String, Biome)> = Vec::new();
+
+        for path in paths {
+            let contents = match read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    self.push(&path, None, format!("File read error: {}", e));
+                    continue;
+                }
+            };
+            let biome = match parse_biome(&contents) {
+                Ok(biome) => biome,
+                Err(e) => {
+                    let location = e.span().map(|span| line_column(&contents, span.start));
+                    self.push(&path, location, e.message().to_string());
+                    continue;
+                }
+            };
+
+            if let Some(first) = names.get(&biome.name) {
+                let message = format!(
+                    "Duplicate biome '{}', already defined in {}",
+                    biome.name,
+                    first.display()
+                );
+                self.push(&path, locate(&contents, None, "name"), message);
+                continue;
+            }
+            names.insert(biome.name.clone(), path.clone());
+
+            for (layer, condition) in biome.conditions.iter().flatten() {
+                let location = locate(&contents, Some("conditions"), layer);
+                if let Some(message) =
+                    check_condition(layer, condition, biome.is_enabled(), settings)
+                {
+                    self.push(&path, location, message);
+                }
+            }
+
+            if biome.is_enabled() && biome.conditions.is_some() {
+                enabled.push((path, contents, biome));
+            }
+        }
+
+        for (i, (path, contents, biome)) in enabled.iter().enumerate() {
+            for (_, _, other) in &enabled[..i] {
+                if overlap(biome, other) {
+                    let message = format!(
+                        "Conditions of '{}' overlap with '{}', the first by name wins",
+                        biome.name, other.name
+                    );
+                    self.push(path, locate_table(contents, "conditions"), message);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check_condition(
+    layer: &str,
+    condition: &Condition,
+    enabled: bool,
+    settings: &Settings,
+) -> Option<String> {
+    let [min, max] = condition.bounds();
+    if min.is_nan() || max.is_nan() || min > max {
+        return Some(format!(
+            "Impossible `{}` condition: [{}, {}] is empty",
+            layer, min, max
+        ));
+    }
+    if !enabled {
+        return None;
+    }
+
+    let Some([layer_min, layer_max]) = layer_range(layer, settings) else {
+        return Some(format!(
+            "Unknown layer `{}`, the biome never matches",
+            layer
+        ));
+    };
+    if max < layer_min || min > layer_max {
+        return Some(format!(
+            "Impossible `{}` condition: [{}, {}] is outside of the layer values [{}, {}]",
+            layer, min, max, layer_min, layer_max
+        ));
+    }
+
+    None
+}
+
+/// Whether ranges of tiles match both biomes. Ranges sharing only a bound, like
+/// the `[-20, 0]` of ocean and the `[0, 20]` of land, are not reported: the
+/// tiles at exactly that value go to the first biome by name, as for any
+/// overlap, which is what adjacent ranges are written for.
+fn overlap(a: &Biome, b: &Biome) -> bool {
+    let (Some(a), Some(b)) = (&a.conditions, &b.conditions) else {
+        return false;
+    };
+
+    a.iter().all(|(layer, a)| match b.get(layer) {
+        Some(b) => {
+            let ([a_min, a_max], [b_min, b_max]) = (a.bounds(), b.bounds());
+            a_min.max(b_min) < a_max.min(b_max)
+        }
+        None => true,
+    })
+}
+
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+
+    (line, column)
+}
+
+fn locate_table(contents: &str, table: &str) -> Option<(usize, usize)> {
+    let header = format!("[{}]", table);
+    contents
+        .lines()
+        .position(|line| line.trim() == header)
+        .map(|index| (index + 1, 1))
+}
+
+/// Finds the line of the header of the table at `index` in an array of tables.
+fn locate_array_table(contents: &str, array: &str, index: usize) -> Option<(usize, usize)> {
+    let header = format!("[[{}]]", array);
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim() == header)
+        .nth(index)
+        .map(|(line, _)| (line + 1, 1))
+}
+
+/// Finds the line of `key = ...`, in a table or before any table.
+fn locate(contents: &str, table: Option<&str>, key: &str) -> Option<(usize, usize)> {
+    let start = match table {
+        Some(table) => locate_table(contents, table)?.0,
+        None => 0,
+    };
+
+    contents
+        .lines()
+        .enumerate()
+        .skip(start)
+        .take_while(|(_, line)| !line.trim_start().starts_with('['))
+        .find_map(|(index, line)| {
+            let trimmed = line.trim_start();
+            let is_key = trimmed
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='));
+            is_key.then(|| (index + 1, line.len() - trimmed.len() + 1))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+    use std::path::Path;
+
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    use crate::worlds::map::biomes::BIOMES_PATH;
+    use crate::worlds::settings::{Settings, PRESETS_PATH};
+
+    use super::{line_column, Report};
+
+    fn validate_biomes(files: &[(&str, &str)]) -> Report {
+        let dir = tempdir().unwrap();
+        for (name, contents) in files {
+            write(dir.path().join(name), contents).unwrap();
+        }
+
+        let mut report = Report::default();
+        report
+            .validate_biomes(dir.path(), &Settings::from_seed(0))
+            .unwrap();
+        report
+    }
+
+    #[rstest]
+    fn test_shipped_files_are_valid() {
+        let mut report = Report::default();
+        for entry in std::fs::read_dir(PRESETS_PATH).unwrap() {
+            report.validate_settings(&entry.unwrap().path());
+        }
+        report
+            .validate_biomes(Path::new(BIOMES_PATH), &Settings::from_seed(0))
+            .unwrap();
+
+        assert!(report.is_ok(), "{:#?}", report.issues);
+    }
+
+    #[rstest]
+    fn test_parse_error_location() {
+        let report =
+            validate_biomes(&[("a.toml", "name = \"A\"\n\n[tiles]\ngrass = [1, 2, 300]\n")]);
+
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].location.map(|(line, _)| line), Some(4));
+    }
+
+    #[rstest]
+    fn test_duplicate_names() {
+        let report = validate_biomes(&[("a.toml", "name = \"A\""), ("b.toml", "name = \"A\"")]);
+
+        assert_eq!(report.issues.len(), 1);
+        assert!(report.issues[0].path.ends_with("b.toml"));
+        assert_eq!(report.issues[0].location, Some((1, 1)));
+    }
+
+    #[rstest]
+    #[case::empty_range("elevation = [10, -10]")]
+    #[case::outside_layer("elevation = [100, 200]")]
+    #[case::unknown_layer("humidity = [0, 10]")]
+    fn test_impossible_conditions(#[case] condition: &str) {
+        let contents = format!("name = \"A\"\n\n[conditions]\n{}\n", condition);
+        let report = validate_biomes(&[("a.toml", &contents)]);
+
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].location, Some((4, 1)));
+    }
+
+    #[rstest]
+    fn test_disabled_biomes_conditions_are_not_checked() {
+        let contents = "name = \"A\"\nenabled = false\n\n[conditions]\nhumidity = 10\n";
+        let report = validate_biomes(&[("a.toml", contents)]);
+
+        assert!(report.is_ok());
+    }
+
+    #[rstest]
+    #[case::overlapping("elevation = [-5, 5]", 1)]
+    #[case::touching("elevation = [0, 20]", 0)]
+    #[case::other_layer("temperature = [0, 10]", 1)]
+    fn test_overlapping_conditions(#[case] condition: &str, #[case] expected: usize) {
+        let a = "name = \"A\"\n[conditions]\nelevation = [-20, 0]\n";
+        let b = format!("name = \"B\"\n[conditions]\n{}\n", condition);
+        let report = validate_biomes(&[("a.toml", a), ("b.toml", &b)]);
+
+        assert_eq!(report.issues.len(), expected);
+    }
+
+    #[rstest]
+    fn test_invalid_settings() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        write(&path, "width = 0\n\n[elevation_gen]\nnoise_scale = 100.0\noctaves = 0\nlacunarity = 2.0\npersistance = 0.5\noffset = [0.0, 0.0]\n").unwrap();
+
+        let mut report = Report::default();
+        report.validate_settings(&path);
+
+        let locations: Vec<_> = report.issues.iter().map(|issue| issue.location).collect();
+        assert_eq!(locations, [Some((1, 1)), Some((5, 1))]);
+    }
+
+    #[rstest]
+    fn test_invalid_steps() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        write(&path, "width = 10\n\n[[steps]]\nkind = \"elevation\"\n\n[[steps]]\nkind = \"biomes\"\n\n[[steps]]\nkind = \"erosion\"\niterations = 2\ntalus = 0.1\n").unwrap();
+
+        let mut report = Report::default();
+        report.validate_settings(&path);
+
+        let issues: Vec<_> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.location, issue.message.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (
+                    Some((6, 1)),
+                    "`biomes` needs `temperature`, which no previous step outputs"
+                ),
+                (Some((6, 1)), "`biomes` must be the last step"),
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_line_column() {
+        assert_eq!(line_column("a = 1\nbb = 2", 8), (2, 3));
+    }
+}